@@ -0,0 +1,58 @@
+use base64::Engine;
+use eyre::{Result, WrapErr};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// Copies `text` with an OSC 52 escape sequence, which the terminal turns into a clipboard write
+// even over SSH. Terminals without support ignore it silently, so when `command` is configured
+// (e.g. `wl-copy` or `xclip -selection clipboard`) the text is also piped to it.
+pub fn copy(text: &str, command: Option<&str>, terminal: &mut impl Write) -> Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    write!(terminal, "\x1b]52;c;{}\x07", encoded)?;
+    terminal.flush()?;
+
+    if let Some(command) = command {
+        copy_with_command(text, command)
+            .wrap_err_with(|| format!("Clipboard command '{}' failed", command))?;
+    }
+    Ok(())
+}
+
+fn copy_with_command(text: &str, command: &str) -> Result<()> {
+    let mut parts = command.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| eyre::eyre!("The command is empty"))?;
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        eyre::bail!("Exited with {}", status);
+    }
+    Ok(())
+}
+
+// `path` relative to `base`, climbing out with `..` where they diverge. Both must be absolute.
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let path: Vec<Component> = path.components().collect();
+    let base: Vec<Component> = base.components().collect();
+    let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..base.len() {
+        relative.push("..");
+    }
+    relative.extend(&path[common..]);
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
@@ -1,3 +1,4 @@
+use crate::db::MatchMode;
 use eyre::Result;
 use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
@@ -11,6 +12,27 @@ pub struct Config {
     pub depth: usize,
     pub highlight_color: Option<String>,
     pub editor: Option<String>,
+    #[serde(default = "default_search_dirs")]
+    pub search_dirs: bool,
+    #[serde(default)]
+    pub threads: usize,
+    #[serde(default)]
+    pub respect_ignore_files: bool,
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    #[serde(default)]
+    pub match_mode: MatchMode,
+    #[serde(default = "default_preview")]
+    pub preview: bool,
+    pub clipboard_command: Option<String>,
+}
+
+fn default_search_dirs() -> bool {
+    true
+}
+
+fn default_preview() -> bool {
+    true
 }
 
 impl Default for Config {
@@ -67,7 +89,36 @@ impl Default for Config {
             depth: 10,
             highlight_color: None,
             editor: None, // vi, vim, nvim, subl, code, etc.
+            search_dirs: default_search_dirs(),
+            threads: 0, // 0 picks a worker count based on the available CPUs
+            respect_ignore_files: false, // .gitignore, .ignore and .quickfindignore
+            follow_symlinks: false, // Descend into symlinked directories
+            match_mode: MatchMode::Substring,
+            preview: default_preview(),
+            clipboard_command: None, // wl-copy, xclip -selection clipboard, pbcopy, etc.
+        }
+    }
+}
+
+impl Config {
+    // Identifies the settings that decide what gets indexed under a root, so the run history
+    // shows when they changed. FNV-1a rather than `DefaultHasher`, whose output may change
+    // between Rust releases.
+    pub fn index_hash(&self) -> String {
+        let mut settings = format!(
+            "{:?}|{}|{}",
+            self.ignore, self.depth, self.respect_ignore_files
+        );
+        // Only appended when set, so hashes recorded before the option existed still match.
+        if self.follow_symlinks {
+            settings.push_str("|follow_symlinks");
+        }
+        let mut hash: u64 = 0xcbf29ce484222325;
+        for byte in settings.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
         }
+        format!("{:016x}", hash)
     }
 }
 
@@ -85,7 +136,7 @@ pub fn load_config() -> Result<Config> {
         let toml_string = toml::to_string(&default_config)?;
         let mut file = File::create(&config_path)?;
         file.write_all(toml_string.as_bytes())?;
-        println!("Created default config at {:?}", config_path);
+        eprintln!("Created default config at {:?}", config_path);
         return Ok(default_config);
     }
 
@@ -1,6 +1,18 @@
+use crate::query;
+use crate::rank::{Ranker, ScoredEntry};
 use eyre::Result;
-use rusqlite::{params, Connection, Result as RusqliteResult};
-use std::path::PathBuf;
+use regex::Regex;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, Result as RusqliteResult};
+use serde::{Deserialize, Serialize};
+use std::path::{PathBuf, MAIN_SEPARATOR};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const SEARCH_BATCH_SIZE: usize = 1000; // First batch handed over while streaming
+const SEARCH_BATCH_INTERVAL: Duration = Duration::from_millis(50); // Or sooner, when rows trickle in
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30); // How long a writer waits for another to commit
 
 pub fn get_db_path() -> Result<PathBuf> {
     let home_dir = home::home_dir().ok_or_else(|| eyre::eyre!("Could not find home directory"))?;
@@ -12,10 +24,111 @@ pub fn get_db_path() -> Result<PathBuf> {
 pub fn get_connection() -> Result<Connection> {
     let db_path = get_db_path()?;
     let conn = Connection::open(db_path)?;
+    // An index run, `index --watch` and the TUI's search thread can all have the database open at
+    // once. In WAL mode readers and the writer don't block each other, and a second writer waits
+    // for the first to commit instead of failing right away.
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    register_regexp(&conn)?;
     Ok(conn)
 }
 
-pub fn create_tables(conn: &Connection) -> RusqliteResult<()> {
+// Backs SQLite's `path REGEXP pattern` operator, which calls `regexp(pattern, path)`.
+// The compiled pattern is cached as auxiliary data, so it's built once per statement rather than per row.
+fn register_regexp(conn: &Connection) -> RusqliteResult<()> {
+    conn.create_scalar_function(
+        "regexp",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let regex: Arc<Regex> = ctx.get_or_create_aux(0, |pattern| -> Result<Regex> {
+                Ok(Regex::new(pattern.as_str()?)?)
+            })?;
+            let text = ctx.get_raw(1).as_str_or_null()?;
+            Ok(text.is_some_and(|text| regex.is_match(text)))
+        },
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+impl EntryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntryKind::File => "file",
+            EntryKind::Dir => "dir",
+            EntryKind::Symlink => "symlink",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "file" => Some(EntryKind::File),
+            "dir" => Some(EntryKind::Dir),
+            "symlink" => Some(EntryKind::Symlink),
+            _ => None,
+        }
+    }
+}
+
+// Metadata columns are NULL for rows indexed before they existed, until the next index run fills them in.
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub path: String,
+    pub size: Option<u64>,
+    pub mtime: Option<i64>,
+    pub kind: Option<EntryKind>,
+    pub extension: Option<String>,
+    pub link_target: Option<String>, // What a symlink points to, as written in the link
+}
+
+pub enum InsertOutcome {
+    Added,
+    Updated,
+    Unchanged,
+}
+
+// `MIGRATIONS[i]` upgrades a database from schema version `i` to `i + 1`.
+// Append new migrations to the end; never edit or reorder ones that have shipped.
+const MIGRATIONS: &[fn(&Connection) -> RusqliteResult<()>] = &[
+    create_files_table,
+    add_file_metadata_columns,
+    create_extension_index,
+    create_path_trigram_index,
+    create_index_runs_table,
+    add_index_run_details,
+    create_walk_errors_table,
+    add_link_target_column,
+];
+
+pub fn run_migrations(conn: &Connection) -> Result<()> {
+    let latest_version = MIGRATIONS.len() as i64;
+    let current_version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    if current_version > latest_version {
+        eyre::bail!(
+            "The index database has schema version {}, but this version of quickfind only supports up to {}. Please upgrade quickfind.",
+            current_version,
+            latest_version
+        );
+    }
+
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(current_version as usize) {
+        // Each step commits together with its version bump, so an interrupted upgrade resumes where it stopped.
+        let tx = conn.unchecked_transaction()?;
+        migration(&tx)?;
+        tx.pragma_update(None, "user_version", version as i64 + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+fn create_files_table(conn: &Connection) -> RusqliteResult<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS files (
              id INTEGER PRIMARY KEY,
@@ -26,57 +139,699 @@ pub fn create_tables(conn: &Connection) -> RusqliteResult<()> {
     Ok(())
 }
 
-pub fn insert_file(conn: &Connection, path: &str) -> RusqliteResult<usize> {
+// Databases from before schema versioning may already have some of these columns.
+fn add_file_metadata_columns(conn: &Connection) -> RusqliteResult<()> {
+    for (name, sql_type) in [
+        ("size", "INTEGER"),
+        ("mtime", "INTEGER"),
+        ("kind", "TEXT"),
+        ("extension", "TEXT"),
+    ] {
+        add_column_if_missing(conn, "files", name, sql_type)?;
+    }
+    Ok(())
+}
+
+// Backs `ext:` filters, which compare the column directly.
+fn create_extension_index(conn: &Connection) -> RusqliteResult<()> {
     conn.execute(
-        "INSERT OR IGNORE INTO files (path) VALUES (?1)",
-        params![path],
+        "CREATE INDEX IF NOT EXISTS files_extension ON files (extension)",
+        [],
+    )?;
+    Ok(())
+}
+
+// An FTS5 index over every three-character sequence in `files.path`, so substring searches can
+// look up candidate rows instead of scanning the table.
+// FTS5 writes out a new segment at the end of every statement that changes it, which makes a per-row
+// insert trigger several times slower than the rest of indexing. New rows are queued in
+// `files_fts_pending` instead and added in bulk by `flush_path_index`; searches treat queued rows as
+// candidates, so nothing is missed if a writer stops before flushing.
+fn create_path_trigram_index(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS files_fts USING fts5(
+             path,
+             content = 'files',
+             content_rowid = 'id',
+             tokenize = 'trigram'
+         );
+         CREATE TABLE IF NOT EXISTS files_fts_pending (id INTEGER PRIMARY KEY);
+         CREATE TRIGGER IF NOT EXISTS files_fts_insert AFTER INSERT ON files BEGIN
+             INSERT OR IGNORE INTO files_fts_pending (id) VALUES (new.id);
+         END;
+         CREATE TRIGGER IF NOT EXISTS files_fts_delete AFTER DELETE ON files BEGIN
+             INSERT INTO files_fts (files_fts, rowid, path)
+                 SELECT 'delete', old.id, old.path
+                 WHERE NOT EXISTS (SELECT 1 FROM files_fts_pending WHERE id = old.id);
+             DELETE FROM files_fts_pending WHERE id = old.id;
+         END;
+         CREATE TRIGGER IF NOT EXISTS files_fts_update AFTER UPDATE OF path ON files BEGIN
+             INSERT INTO files_fts (files_fts, rowid, path)
+                 SELECT 'delete', old.id, old.path
+                 WHERE NOT EXISTS (SELECT 1 FROM files_fts_pending WHERE id = old.id);
+             INSERT OR IGNORE INTO files_fts_pending (id) VALUES (new.id);
+         END;
+         INSERT INTO files_fts (files_fts) VALUES ('rebuild');",
     )
 }
 
-// Updated search_files function to handle specific search patterns.
-pub fn search_files(conn: &Connection, term: &str) -> RusqliteResult<Vec<String>> {
-    let mut files = Vec::new();
-    let mut stmt;
-
-    if term.starts_with('.') {
-        // General case for terms starting with '.' (e.g., '.mp3', '.config')
-        // Match paths that END with the term (e.g., '.config' matches 'my/path/.config').
-        // We assume terms starting with '.' are literal and do not contain SQL wildcards.
-        let search_term = format!("%{}", term);
-        stmt = conn.prepare("SELECT path FROM files WHERE path LIKE ?1")?;
-        let mut rows = stmt.query(params![search_term])?;
-        while let Some(row) = rows.next()? {
-            files.push(row.get(0)?);
-        }
+// One row per `index_files` run, so `stats` can tell when each root was last indexed.
+fn create_index_runs_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS index_runs (
+             id INTEGER PRIMARY KEY,
+             root TEXT NOT NULL,
+             started_at INTEGER NOT NULL,
+             duration_ms INTEGER NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS index_runs_root ON index_runs (root);",
+    )
+}
+
+// Runs recorded before these columns existed keep NULL counters; only their end time can be worked out.
+fn add_index_run_details(conn: &Connection) -> RusqliteResult<()> {
+    for (name, sql_type) in [
+        ("finished_at", "INTEGER"),
+        ("files_found", "INTEGER"),
+        ("dirs_traversed", "INTEGER"),
+        ("items_ignored", "INTEGER"),
+        ("entries_added", "INTEGER"),
+        ("entries_updated", "INTEGER"),
+        ("entries_removed", "INTEGER"),
+        ("errors", "INTEGER"),
+        ("config_hash", "TEXT"),
+    ] {
+        add_column_if_missing(conn, "index_runs", name, sql_type)?;
+    }
+    conn.execute(
+        "UPDATE index_runs SET finished_at = started_at + duration_ms / 1000 WHERE finished_at IS NULL",
+        [],
+    )?;
+    Ok(())
+}
+
+// Paths the latest run of each root couldn't index, and why.
+fn create_walk_errors_table(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS walk_errors (
+             id INTEGER PRIMARY KEY,
+             run_id INTEGER NOT NULL REFERENCES index_runs (id),
+             path TEXT NOT NULL,
+             kind TEXT NOT NULL,
+             message TEXT NOT NULL
+         );
+         CREATE INDEX IF NOT EXISTS walk_errors_run_id ON walk_errors (run_id);",
+    )
+}
+
+fn add_link_target_column(conn: &Connection) -> RusqliteResult<()> {
+    add_column_if_missing(conn, "files", "link_target", "TEXT")
+}
+
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    sql_type: &str,
+) -> RusqliteResult<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, sql_type),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn insert_file(conn: &Connection, entry: &FileEntry) -> RusqliteResult<InsertOutcome> {
+    let kind = entry.kind.map(|k| k.as_str());
+    // Called once per indexed entry, so both statements are cached on the connection.
+    let inserted = conn
+        .prepare_cached(
+            "INSERT OR IGNORE INTO files (path, size, mtime, kind, extension, link_target)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?
+        .execute(params![
+            entry.path,
+            entry.size,
+            entry.mtime,
+            kind,
+            entry.extension,
+            entry.link_target
+        ])?;
+    if inserted > 0 {
+        return Ok(InsertOutcome::Added);
+    }
+
+    let updated = conn
+        .prepare_cached(
+            "UPDATE files SET size = ?2, mtime = ?3, kind = ?4, extension = ?5, link_target = ?6
+             WHERE path = ?1
+               AND (size IS NOT ?2 OR mtime IS NOT ?3 OR kind IS NOT ?4 OR extension IS NOT ?5
+                    OR link_target IS NOT ?6)",
+        )?
+        .execute(params![
+            entry.path,
+            entry.size,
+            entry.mtime,
+            kind,
+            entry.extension,
+            entry.link_target
+        ])?;
+    if updated > 0 {
+        Ok(InsertOutcome::Updated)
     } else {
-        // General case for terms not starting with '.'
-        // Split the term into words and search for each word independently.
-        let search_words: Vec<String> = term
-            .split_whitespace()
-            .map(|s| s.replace('*', "%").replace('?', "_").to_lowercase())
-            .collect();
-
-        if search_words.is_empty() {
-            return Ok(vec![]);
+        Ok(InsertOutcome::Unchanged)
+    }
+}
+
+// Writers call this before committing; see `create_path_trigram_index`.
+pub fn flush_path_index(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute_batch(
+        "INSERT INTO files_fts (rowid, path)
+             SELECT id, path FROM files WHERE id IN (SELECT id FROM files_fts_pending);
+         DELETE FROM files_fts_pending;",
+    )
+}
+
+const ENTRY_COLUMNS: &str = "path, size, mtime, kind, extension, link_target";
+
+fn entry_from_row(row: &rusqlite::Row) -> RusqliteResult<FileEntry> {
+    let kind: Option<String> = row.get(3)?;
+    Ok(FileEntry {
+        path: row.get(0)?,
+        size: row.get(1)?,
+        mtime: row.get(2)?,
+        kind: kind.as_deref().and_then(EntryKind::parse),
+        extension: row.get(4)?,
+        link_target: row.get(5)?,
+    })
+}
+
+pub fn delete_file(conn: &Connection, path: &str) -> RusqliteResult<usize> {
+    conn.prepare_cached("DELETE FROM files WHERE path = ?1")?
+        .execute(params![path])
+}
+
+// Removes `path` and, if it was a directory, everything that was indexed beneath it.
+pub fn delete_tree(conn: &Connection, path: &str) -> RusqliteResult<usize> {
+    let prefix = format!(
+        "{}{}",
+        path.trim_end_matches(MAIN_SEPARATOR),
+        MAIN_SEPARATOR
+    );
+    conn.execute(
+        "DELETE FROM files WHERE path = ?1 OR substr(path, 1, length(?2)) = ?2",
+        params![path, prefix],
+    )
+}
+
+// Returns every indexed path that starts with `prefix`.
+// `substr` is used instead of LIKE so that '%' and '_' in directory names are matched literally.
+pub fn paths_under(conn: &Connection, prefix: &str) -> RusqliteResult<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT path FROM files WHERE substr(path, 1, length(?1)) = ?1")?;
+    let rows = stmt.query_map(params![prefix], |row| row.get(0))?;
+    rows.collect()
+}
+
+pub fn all_paths(conn: &Connection) -> RusqliteResult<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT path FROM files")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+// Rows indexed before `kind` existed are counted as files, which they always are.
+pub fn count_by_kind(conn: &Connection) -> RusqliteResult<Vec<(EntryKind, usize)>> {
+    let mut stmt = conn
+        .prepare("SELECT COALESCE(kind, 'file') AS k, COUNT(*) FROM files GROUP BY k ORDER BY k")?;
+    let rows = stmt.query_map([], |row| {
+        let kind: String = row.get(0)?;
+        Ok((
+            EntryKind::parse(&kind).unwrap_or(EntryKind::File),
+            row.get(1)?,
+        ))
+    })?;
+    rows.collect()
+}
+
+// Counters are `None` for runs recorded before they were kept.
+pub struct IndexRun {
+    pub root: String,
+    pub started_at: i64, // Seconds since the Unix epoch
+    pub finished_at: i64,
+    pub duration_ms: i64,
+    pub files_found: Option<usize>,
+    pub dirs_traversed: Option<usize>,
+    pub items_ignored: Option<usize>,
+    pub entries_added: Option<usize>,
+    pub entries_updated: Option<usize>,
+    pub entries_removed: Option<usize>,
+    pub errors: Option<usize>,
+    pub config_hash: Option<String>, // See `Config::index_hash`
+}
+
+const INDEX_RUN_COLUMNS: &str = "root, started_at, finished_at, duration_ms, files_found, \
+     dirs_traversed, items_ignored, entries_added, entries_updated, entries_removed, errors, config_hash";
+
+fn index_run_from_row(row: &rusqlite::Row) -> RusqliteResult<IndexRun> {
+    Ok(IndexRun {
+        root: row.get(0)?,
+        started_at: row.get(1)?,
+        finished_at: row.get(2)?,
+        duration_ms: row.get(3)?,
+        files_found: row.get(4)?,
+        dirs_traversed: row.get(5)?,
+        items_ignored: row.get(6)?,
+        entries_added: row.get(7)?,
+        entries_updated: row.get(8)?,
+        entries_removed: row.get(9)?,
+        errors: row.get(10)?,
+        config_hash: row.get(11)?,
+    })
+}
+
+// Returns the id of the new run.
+pub fn record_index_run(conn: &Connection, run: &IndexRun) -> RusqliteResult<i64> {
+    conn.execute(
+        &format!(
+            "INSERT INTO index_runs ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            INDEX_RUN_COLUMNS
+        ),
+        params![
+            run.root,
+            run.started_at,
+            run.finished_at,
+            run.duration_ms,
+            run.files_found,
+            run.dirs_traversed,
+            run.items_ignored,
+            run.entries_added,
+            run.entries_updated,
+            run.entries_removed,
+            run.errors,
+            run.config_hash
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+// Something the walk couldn't index. `path` is empty when the walker didn't say where it happened.
+#[derive(Debug, Clone)]
+pub struct WalkError {
+    pub path: String,
+    pub kind: String,
+    pub message: String,
+}
+
+// Replaces the errors kept for `root`: only its latest run says what's currently missing.
+pub fn record_walk_errors(
+    conn: &Connection,
+    run_id: i64,
+    root: &str,
+    errors: &[WalkError],
+) -> RusqliteResult<()> {
+    conn.execute(
+        "DELETE FROM walk_errors WHERE run_id IN (SELECT id FROM index_runs WHERE root = ?1 AND id != ?2)",
+        params![root, run_id],
+    )?;
+    let mut stmt = conn
+        .prepare("INSERT INTO walk_errors (run_id, path, kind, message) VALUES (?1, ?2, ?3, ?4)")?;
+    for error in errors {
+        stmt.execute(params![run_id, error.path, error.kind, error.message])?;
+    }
+    Ok(())
+}
+
+// Errors from the latest run of every root, as (root, error) pairs ordered by path.
+pub fn walk_errors(conn: &Connection) -> RusqliteResult<Vec<(String, WalkError)>> {
+    let mut stmt = conn.prepare(
+        "SELECT index_runs.root, walk_errors.path, walk_errors.kind, walk_errors.message
+         FROM walk_errors JOIN index_runs ON index_runs.id = walk_errors.run_id
+         ORDER BY index_runs.root, walk_errors.path",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get(0)?,
+            WalkError {
+                path: row.get(1)?,
+                kind: row.get(2)?,
+                message: row.get(3)?,
+            },
+        ))
+    })?;
+    rows.collect()
+}
+
+// The most recent run for every root that has been indexed, oldest first.
+pub fn last_index_runs(conn: &Connection) -> RusqliteResult<Vec<IndexRun>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM index_runs
+         WHERE id IN (SELECT MAX(id) FROM index_runs GROUP BY root)
+         ORDER BY started_at",
+        INDEX_RUN_COLUMNS
+    ))?;
+    let rows = stmt.query_map([], index_run_from_row)?;
+    rows.collect()
+}
+
+// Newest first, optionally only the runs for `root`.
+pub fn index_history(
+    conn: &Connection,
+    root: Option<&str>,
+    limit: usize,
+) -> RusqliteResult<Vec<IndexRun>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM index_runs WHERE ?1 IS NULL OR root = ?1 ORDER BY id DESC LIMIT ?2",
+        INDEX_RUN_COLUMNS
+    ))?;
+    let rows = stmt.query_map(params![root, limit as i64], index_run_from_row)?;
+    rows.collect()
+}
+
+// Counts `root` itself and everything beneath it, with the same literal matching as `paths_under`.
+pub fn count_under(conn: &Connection, root: &str) -> RusqliteResult<usize> {
+    let prefix = format!(
+        "{}{}",
+        root.trim_end_matches(MAIN_SEPARATOR),
+        MAIN_SEPARATOR
+    );
+    conn.query_row(
+        "SELECT COUNT(*) FROM files WHERE path = ?1 OR substr(path, 1, length(?2)) = ?2",
+        params![root, prefix],
+        |row| row.get(0),
+    )
+}
+
+pub struct ExtensionStats {
+    pub extension: String,
+    pub count: usize,
+    pub size: u64,
+}
+
+// The `limit` most common extensions, or the ones taking up the most space when `by_size` is set.
+pub fn top_extensions(
+    conn: &Connection,
+    by_size: bool,
+    limit: usize,
+) -> RusqliteResult<Vec<ExtensionStats>> {
+    let order = if by_size { "size" } else { "count" };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT extension, COUNT(*) AS count, COALESCE(SUM(size), 0) AS size FROM files
+         WHERE extension IS NOT NULL AND kind IS NOT 'dir'
+         GROUP BY extension ORDER BY {} DESC, extension LIMIT ?1",
+        order
+    ))?;
+    let rows = stmt.query_map(params![limit as i64], |row| {
+        Ok(ExtensionStats {
+            extension: row.get(0)?,
+            count: row.get(1)?,
+            size: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn schema_version(conn: &Connection) -> RusqliteResult<i64> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+pub fn vacuum(conn: &Connection) -> RusqliteResult<()> {
+    conn.execute_batch("INSERT INTO files_fts (files_fts) VALUES ('optimize'); VACUUM;")
+}
+
+// Returns the problems found, if any. Queued rows are flushed first, since the search index is
+// checked against the `files` table and would otherwise report them as missing.
+pub fn check_integrity(conn: &Connection) -> Result<Vec<String>> {
+    let tx = conn.unchecked_transaction()?;
+    flush_path_index(&tx)?;
+    tx.commit()?;
+
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let mut problems = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .filter(|row| !matches!(row.as_deref(), Ok("ok")))
+        .collect::<RusqliteResult<Vec<_>>>()?;
+    if let Err(e) = conn.execute(
+        "INSERT INTO files_fts (files_fts, rank) VALUES ('integrity-check', 1)",
+        [],
+    ) {
+        problems.push(format!("Search index: {}", e));
+    }
+    Ok(problems)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    #[default]
+    Substring,
+    Fuzzy,
+    Regex,
+}
+
+pub const FUZZY_PREFIX: &str = "fz:";
+pub const REGEX_PREFIX: &str = "re:";
+
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub include_dirs: bool,
+    pub mode: MatchMode,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            include_dirs: true,
+            mode: MatchMode::Substring,
         }
+    }
+}
 
-        let mut query = "SELECT path FROM files WHERE ".to_string();
-        let mut params_vec: Vec<String> = Vec::new();
+// A query can pick its own match mode with a prefix, overriding the configured one.
+pub fn split_mode_prefix(term: &str, default_mode: MatchMode) -> (MatchMode, &str) {
+    if let Some(rest) = term.strip_prefix(FUZZY_PREFIX) {
+        (MatchMode::Fuzzy, rest)
+    } else if let Some(rest) = term.strip_prefix(REGEX_PREFIX) {
+        (MatchMode::Regex, rest)
+    } else {
+        (default_mode, term)
+    }
+}
 
-        for (i, word) in search_words.iter().enumerate() {
-            if i > 0 {
-                query.push_str(" AND ");
-            }
-            query.push_str(&format!("LOWER(path) LIKE ?{}", i + 1));
-            params_vec.push(format!("%{}%", word));
+// Regex queries match the whole path case-insensitively, like the other modes; `(?-i)` turns that off.
+// Errors are reduced to their last line, which names the problem without the multi-line caret diagram.
+pub fn compile_regex(pattern: &str) -> Result<Regex> {
+    Regex::new(&format!("(?i){}", pattern)).map_err(|e| {
+        let message = e.to_string();
+        let reason = message.lines().last().unwrap_or_default();
+        eyre::eyre!(
+            "Invalid regular expression: {}",
+            reason.trim_start_matches("error: ")
+        )
+    })
+}
+
+struct SearchPlan {
+    sql: String,
+    params: Vec<Value>,
+    ranker: Ranker,
+}
+
+// Returns `None` for a query with nothing to search for.
+fn plan_search(term: &str, options: &SearchOptions) -> Result<Option<SearchPlan>> {
+    let (mode, term) = split_mode_prefix(term, options.mode);
+    let (condition, query_params, terms) = if mode == MatchMode::Regex {
+        // The whole term is one pattern: the query syntax would otherwise claim its spaces, quotes and parentheses.
+        if term.is_empty() {
+            return Ok(None);
+        }
+        // Compiled here first so that a bad pattern is reported as such, not as a failed statement.
+        let regex = compile_regex(term)?;
+        (
+            "path REGEXP ?".to_string(),
+            vec![Value::Text(regex.as_str().to_string())],
+            Vec::new(),
+        )
+    } else {
+        let query = query::parse(term)?;
+        if query.is_empty() {
+            return Ok(None);
         }
+        let (condition, query_params) = query.to_sql(mode);
+        (condition, query_params, query.text_terms())
+    };
 
-        let mut stmt = conn.prepare(&query)?;
-        let mut rows = stmt.query(rusqlite::params_from_iter(params_vec))?;
-        while let Some(row) = rows.next()? {
-            files.push(row.get(0)?);
+    let mut sql = format!("SELECT {} FROM files WHERE {}", ENTRY_COLUMNS, condition);
+    if !options.include_dirs {
+        // Rows indexed before `kind` existed are NULL and are always files.
+        sql.push_str(" AND kind IS NOT 'dir'");
+    }
+    Ok(Some(SearchPlan {
+        sql,
+        params: query_params,
+        ranker: Ranker::new(&terms, mode),
+    }))
+}
+
+// Parses `term` as a query (see `query`) and returns matching entries, best match first.
+pub fn search_files(
+    conn: &Connection,
+    term: &str,
+    options: &SearchOptions,
+) -> Result<Vec<ScoredEntry>> {
+    let mut results = Vec::new();
+    search_files_in_batches(conn, term, options, |batch| {
+        results.extend(batch);
+        true
+    })?;
+    results.sort();
+    Ok(results)
+}
+
+// Like `search_files`, but hands over results as rows are read, each batch sorted best match first.
+// Stops early when `on_batch` returns false.
+pub fn search_files_in_batches(
+    conn: &Connection,
+    term: &str,
+    options: &SearchOptions,
+    mut on_batch: impl FnMut(Vec<ScoredEntry>) -> bool,
+) -> Result<()> {
+    let Some(plan) = plan_search(term, options)? else {
+        return Ok(());
+    };
+
+    let mut stmt = conn.prepare(&plan.sql)?;
+    let mut rows = stmt.query(params_from_iter(plan.params))?;
+    let mut batch = Vec::new();
+    let mut batch_limit = SEARCH_BATCH_SIZE;
+    let mut last_sent = Instant::now();
+    while let Some(row) = rows.next()? {
+        batch.push(plan.ranker.rank(entry_from_row(row)?));
+        if batch.len() >= batch_limit || last_sent.elapsed() >= SEARCH_BATCH_INTERVAL {
+            // Batches grow with the results handed over so far, so the receiver merges fewer of them.
+            batch_limit = batch_limit.max(batch.len() * 2);
+            batch.sort();
+            if !on_batch(std::mem::take(&mut batch)) {
+                return Ok(());
+            }
+            last_sent = Instant::now();
         }
     }
-    Ok(files)
+    if !batch.is_empty() {
+        batch.sort();
+        on_batch(batch);
+    }
+    Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn migrated() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn insert(conn: &Connection, path: &str) {
+        let entry = FileEntry {
+            path: path.to_string(),
+            size: Some(0),
+            mtime: Some(0),
+            kind: Some(EntryKind::File),
+            extension: None,
+            link_target: None,
+        };
+        insert_file(conn, &entry).unwrap();
+    }
+
+    fn found(conn: &Connection, term: &str) -> Vec<String> {
+        search_files(conn, term, &SearchOptions::default())
+            .unwrap()
+            .into_iter()
+            .map(|result| result.entry.path)
+            .collect()
+    }
+
+    #[test]
+    fn upgrades_a_database_from_before_schema_versioning() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE files (id INTEGER PRIMARY KEY, path TEXT NOT NULL UNIQUE);
+             INSERT INTO files (path) VALUES ('/home/old/report.txt');",
+        )
+        .unwrap();
+
+        run_migrations(&conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), MIGRATIONS.len() as i64);
+
+        // Rows that were already there are searchable and have no metadata yet.
+        let results = search_files(&conn, "report", &SearchOptions::default()).unwrap();
+        assert_eq!(results.len(), 1);
+        let entry = &results[0].entry;
+        assert_eq!(entry.path, "/home/old/report.txt");
+        assert!(entry.size.is_none() && entry.kind.is_none() && entry.link_target.is_none());
+
+        insert(&conn, "/home/new/report.txt");
+        assert_eq!(found(&conn, "report").len(), 2);
+        assert_eq!(check_integrity(&conn).unwrap(), Vec::<String>::new());
+
+        // Running them again is a no-op.
+        run_migrations(&conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn refuses_a_database_from_a_newer_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        let newer = MIGRATIONS.len() as i64 + 1;
+        conn.pragma_update(None, "user_version", newer).unwrap();
+
+        let err = run_migrations(&conn).unwrap_err().to_string();
+        assert!(err.contains("Please upgrade quickfind"), "{}", err);
+        assert_eq!(schema_version(&conn).unwrap(), newer);
+    }
+
+    #[test]
+    fn searches_find_queued_and_flushed_rows() {
+        let conn = migrated();
+        insert(&conn, "/home/notes/report.txt");
+        assert_eq!(found(&conn, "report"), ["/home/notes/report.txt"]);
+
+        flush_path_index(&conn).unwrap();
+        insert(&conn, "/home/notes/report-2.txt");
+        assert_eq!(
+            found(&conn, "report"),
+            ["/home/notes/report.txt", "/home/notes/report-2.txt"]
+        );
+
+        flush_path_index(&conn).unwrap();
+        assert_eq!(
+            found(&conn, "report"),
+            ["/home/notes/report.txt", "/home/notes/report-2.txt"]
+        );
+        assert!(found(&conn, "missing").is_empty());
+    }
+
+    #[test]
+    fn deleting_queued_and_flushed_rows_keeps_the_search_index_consistent() {
+        let conn = migrated();
+        insert(&conn, "/home/flushed.txt");
+        flush_path_index(&conn).unwrap();
+        insert(&conn, "/home/queued.txt");
+
+        delete_file(&conn, "/home/queued.txt").unwrap();
+        assert!(found(&conn, "queued").is_empty());
+        assert_eq!(check_integrity(&conn).unwrap(), Vec::<String>::new());
+
+        delete_file(&conn, "/home/flushed.txt").unwrap();
+        assert!(found(&conn, "flushed").is_empty());
+        assert_eq!(check_integrity(&conn).unwrap(), Vec::<String>::new());
+    }
+}
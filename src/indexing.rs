@@ -1,15 +1,55 @@
 use crate::config::Config;
-use crate::db;
+use crate::db::{self, EntryKind, FileEntry, InsertOutcome, WalkError};
 use eyre::Result;
 use glob::Pattern;
+use ignore::{ParallelVisitor, ParallelVisitorBuilder, WalkBuilder, WalkParallel, WalkState};
 use rusqlite::Connection;
-use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs;
 use std::io::{self, Write};
-use std::ops::AddAssign;
-use std::path::PathBuf;
-use std::rc::Rc;
-use std::time::Instant;
-use walkdir::WalkDir;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+pub const IGNORE_FILENAME: &str = ".quickfindignore";
+const BATCH_SIZE: usize = 512; // Entries sent to the writer at a time
+const PENDING_BATCHES: usize = 64; // Walkers block once the writer falls this far behind
+const COMMIT_INTERVAL: usize = 20_000; // Rows written per transaction
+
+#[derive(Default)]
+struct WalkCounters {
+    files: AtomicUsize,
+    dirs: AtomicUsize,
+    ignored: AtomicUsize,
+    // Entries found in the directories walked, and how many of those reached the visitor. Only
+    // kept when ignore files are respected, since the walker skips what they match without
+    // passing it to `filter_entry`.
+    listed: AtomicUsize,
+    visited: AtomicUsize,
+}
+
+impl WalkCounters {
+    fn snapshot(&self) -> (usize, usize, usize) {
+        (
+            self.files.load(Ordering::Relaxed),
+            self.dirs.load(Ordering::Relaxed),
+            self.ignored.load(Ordering::Relaxed),
+        )
+    }
+
+    // Everything listed but never visited was skipped by `config.ignore` or an ignore file. While
+    // the walk is running, directories are listed well before their entries are visited, so this
+    // is only meaningful once it's done.
+    fn total_ignored(&self) -> usize {
+        let listed = self.listed.load(Ordering::Relaxed);
+        let visited = self.visited.load(Ordering::Relaxed);
+        let ignored = self.ignored.load(Ordering::Relaxed);
+        ignored.max(listed.saturating_sub(visited))
+    }
+}
 
 pub fn index_files(conn: &Connection, config: &Config, path: &str, verbose: bool) -> Result<()> {
     if verbose {
@@ -17,70 +57,196 @@ pub fn index_files(conn: &Connection, config: &Config, path: &str, verbose: bool
     }
 
     let start_time = Instant::now();
-    let mut files_discovered = 0;
-    let mut dirs_traversed = 0;
-    let items_ignored = Rc::new(RefCell::new(0));
-    let progress_interval = 1000; // Report progress every 1000 items
-    let mut last_report_time = Instant::now();
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let counters = Arc::new(WalkCounters::default());
+    let ignore_patterns = Arc::new(compile_ignore_patterns(config)?);
+    let include_path_buf = PathBuf::from(path);
 
-    let ignore_patterns: Vec<Pattern> = config
-        .ignore
-        .iter()
-        .map(|s| Pattern::new(s))
-        .collect::<Result<Vec<_>, _>>()?;
+    // The parallel walker never passes the root to `filter_entry`, so check it here.
+    let root_ignored = is_ignored(&ignore_patterns, &include_path_buf, &include_path_buf);
+    if root_ignored {
+        if verbose {
+            println!("Skipping ignored path: {:?}", include_path_buf);
+        }
+        counters.ignored.fetch_add(1, Ordering::Relaxed);
+    }
 
-    // Use the provided path for WalkDir
-    let include_path_buf = PathBuf::from(path);
-    let items_ignored_cloned = Rc::clone(&items_ignored);
-    for entry in WalkDir::new(path)
-        .max_depth(config.depth)
-        .into_iter()
-        .filter_entry(move |entry| {
-            let entry_path = entry.path();
-            let mut is_ignored = false;
-
-            // Check against absolute path
-            if ignore_patterns.iter().any(|p| p.matches_path(entry_path)) {
-                is_ignored = true;
-            }
+    // Builds a walker for the root or, when following symlinks, for a linked directory in it.
+    let walker_for = |start: &Path, max_depth: usize| {
+        let counters = Arc::clone(&counters);
+        let ignore_patterns = Arc::clone(&ignore_patterns);
+        let root = include_path_buf.clone();
+        walk_builder(start, config.respect_ignore_files)
+            .max_depth(Some(max_depth))
+            .threads(config.threads)
+            .filter_entry(move |entry| {
+                let entry_path = entry.path();
+                let ignored = is_ignored(&ignore_patterns, &root, entry_path);
 
-            // Check against path relative to the provided path
-            if !is_ignored {
-                if let Ok(relative_path) = entry_path.strip_prefix(&include_path_buf) {
-                    if ignore_patterns
-                        .iter()
-                        .any(|p| p.matches_path(relative_path))
-                    {
-                        is_ignored = true;
+                if ignored {
+                    if verbose {
+                        println!("Skipping ignored path: {:?}", entry_path);
                     }
+                    counters.ignored.fetch_add(1, Ordering::Relaxed);
                 }
-            }
+                !ignored
+            })
+            .build_parallel()
+    };
 
-            if is_ignored {
-                if verbose {
-                    println!("Skipping ignored path: {:?}", entry_path);
+    let (tx, rx) = mpsc::sync_channel(PENDING_BATCHES);
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let mut visitor_builder = BatchingVisitorBuilder {
+        tx,
+        counters: Arc::clone(&counters),
+        errors: Arc::clone(&errors),
+        follow_symlinks: config.follow_symlinks,
+        count_listed: config.respect_ignore_files,
+        max_depth: config.depth,
+        base_depth: 0,
+        walked_dirs: Arc::new(HashSet::new()),
+        linked_dirs: Arc::new(Mutex::new(Vec::new())),
+    };
+
+    // Walker threads feed batches to this thread, which is the only one that writes to SQLite.
+    let summary = thread::scope(|scope| {
+        scope.spawn(|| {
+            if !root_ignored {
+                walker_for(&include_path_buf, config.depth).visit(&mut visitor_builder);
+                if config.follow_symlinks {
+                    walk_linked_dirs(
+                        &include_path_buf,
+                        config.depth,
+                        &mut visitor_builder,
+                        walker_for,
+                    );
                 }
-                items_ignored_cloned.borrow_mut().add_assign(1);
             }
-            !is_ignored
-        })
-        .filter_map(|e| e.ok())
-    {
-        let entry_path = entry.path();
-        if entry_path.is_file() {
-            if let Some(path_str) = entry_path.to_str() {
-                db::insert_file(conn, path_str)?;
-                files_discovered += 1;
-                if verbose {
-                    println!("[{}] Discovered: {}", files_discovered, path_str);
-                }
+            // Hangs up on the writer.
+            drop(visitor_builder);
+        });
+        write_batches(conn, rx, &counters, start_time, verbose)
+    })?;
+
+    if !verbose {
+        print!("\r"); // Clear the last progress line
+        io::stdout().flush()?;
+    }
+
+    // Anything under this root that wasn't seen during the walk has been deleted, moved or is now ignored.
+    let mut errors = std::mem::take(&mut *errors.lock().unwrap_or_else(|e| e.into_inner()));
+    errors.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let tx = conn.unchecked_transaction()?;
+    let entries_removed = prune_unseen(&tx, path, &summary.seen_paths, &errors, verbose)?;
+    let (files_discovered, dirs_traversed, _) = counters.snapshot();
+    let items_ignored = counters.total_ignored();
+    let run_id = db::record_index_run(
+        &tx,
+        &db::IndexRun {
+            root: path.to_string(),
+            started_at,
+            finished_at: started_at + start_time.elapsed().as_secs() as i64,
+            duration_ms: start_time.elapsed().as_millis() as i64,
+            files_found: Some(files_discovered),
+            dirs_traversed: Some(dirs_traversed),
+            items_ignored: Some(items_ignored),
+            entries_added: Some(summary.entries_added),
+            entries_updated: Some(summary.entries_updated),
+            entries_removed: Some(entries_removed),
+            errors: Some(errors.len()),
+            config_hash: Some(config.index_hash()),
+        },
+    )?;
+    db::record_walk_errors(&tx, run_id, path, &errors)?;
+    tx.commit()?;
+
+    println!(
+        "Indexing complete: Found {} files, traversed {} directories, ignored {} items in {:.2?} ({} added, {} updated, {} removed, {} unchanged)",
+        files_discovered,
+        dirs_traversed,
+        items_ignored,
+        start_time.elapsed(),
+        summary.entries_added,
+        summary.entries_updated,
+        entries_removed,
+        summary.seen_paths.len() - summary.entries_added - summary.entries_updated
+    );
+    if !errors.is_empty() {
+        if verbose {
+            println!("{} paths could not be indexed:", errors.len());
+            for error in &errors {
+                println!("  {}: {} ({})", error.path, error.kind, error.message);
             }
-        } else if entry_path.is_dir() {
-            dirs_traversed += 1;
+        } else {
+            println!(
+                "{} paths could not be indexed (run with --verbose or see `quickfind stats` for details)",
+                errors.len()
+            );
         }
+    }
 
-        let total_processed = files_discovered + dirs_traversed + *items_ignored.borrow();
-        if total_processed % progress_interval == 0
+    Ok(())
+}
+
+struct WriteSummary {
+    seen_paths: HashSet<String>,
+    entries_added: usize,
+    entries_updated: usize,
+}
+
+// Takes ownership of `rx` so that returning early on a database error hangs up on the walkers.
+fn write_batches(
+    conn: &Connection,
+    rx: Receiver<Vec<FileEntry>>,
+    counters: &WalkCounters,
+    start_time: Instant,
+    verbose: bool,
+) -> Result<WriteSummary> {
+    let mut summary = WriteSummary {
+        seen_paths: HashSet::new(),
+        entries_added: 0,
+        entries_updated: 0,
+    };
+    let progress_interval = 1000; // Report progress every 1000 items
+    let mut last_reported_total = 0;
+    let mut last_report_time = Instant::now();
+
+    // Rows are committed in large transactions: far fewer fsyncs than autocommit, and an
+    // interrupted run keeps whole transactions rather than whatever rows happened to land.
+    let mut tx = conn.unchecked_transaction()?;
+    let mut uncommitted = 0;
+
+    for batch in rx {
+        uncommitted += batch.len();
+        for file_entry in batch {
+            match db::insert_file(&tx, &file_entry)? {
+                InsertOutcome::Added => summary.entries_added += 1,
+                InsertOutcome::Updated => summary.entries_updated += 1,
+                InsertOutcome::Unchanged => {}
+            }
+            if verbose {
+                println!(
+                    "[{}] Discovered: {}",
+                    summary.seen_paths.len() + 1,
+                    file_entry.path
+                );
+            }
+            summary.seen_paths.insert(file_entry.path);
+        }
+
+        if uncommitted >= COMMIT_INTERVAL {
+            db::flush_path_index(&tx)?;
+            tx.commit()?;
+            tx = conn.unchecked_transaction()?;
+            uncommitted = 0;
+        }
+
+        let (files_discovered, dirs_traversed, items_ignored) = counters.snapshot();
+        let total_processed = files_discovered + dirs_traversed + items_ignored;
+        if total_processed / progress_interval > last_reported_total / progress_interval
             || last_report_time.elapsed().as_secs_f32() > 5.0
         {
             if verbose {
@@ -88,7 +254,7 @@ pub fn index_files(conn: &Connection, config: &Config, path: &str, verbose: bool
                     "Progress: Files: {}, Dirs: {}, Ignored: {}, Elapsed: {:.2?}",
                     files_discovered,
                     dirs_traversed,
-                    *items_ignored.borrow(),
+                    items_ignored,
                     start_time.elapsed()
                 );
             } else {
@@ -96,32 +262,440 @@ pub fn index_files(conn: &Connection, config: &Config, path: &str, verbose: bool
                     "\rIndexing... Files: {}, Dirs: {}, Ignored: {}, Elapsed: {:.2?}",
                     files_discovered,
                     dirs_traversed,
-                    *items_ignored.borrow(),
+                    items_ignored,
                     start_time.elapsed()
                 );
                 io::stdout().flush()?;
             }
+            last_reported_total = total_processed;
             last_report_time = Instant::now();
         }
     }
+    db::flush_path_index(&tx)?;
+    tx.commit()?;
 
-    if !verbose {
-        print!("\r"); // Clear the last progress line
-        io::stdout().flush()?;
+    Ok(summary)
+}
+
+struct BatchingVisitorBuilder {
+    tx: SyncSender<Vec<FileEntry>>,
+    counters: Arc<WalkCounters>,
+    errors: Arc<Mutex<Vec<WalkError>>>,
+    follow_symlinks: bool,
+    count_listed: bool,
+    max_depth: usize,
+    base_depth: usize, // Depth of the directory the current walk started from
+    walked_dirs: Arc<HashSet<FileId>>,
+    linked_dirs: Arc<Mutex<Vec<(PathBuf, usize)>>>,
+}
+
+impl<'s> ParallelVisitorBuilder<'s> for BatchingVisitorBuilder {
+    fn build(&mut self) -> Box<dyn ParallelVisitor + 's> {
+        Box::new(BatchingVisitor {
+            batch: Vec::with_capacity(BATCH_SIZE),
+            tx: self.tx.clone(),
+            counters: Arc::clone(&self.counters),
+            errors: Arc::clone(&self.errors),
+            follow_symlinks: self.follow_symlinks,
+            count_listed: self.count_listed,
+            max_depth: self.max_depth,
+            base_depth: self.base_depth,
+            walked_dirs: Arc::clone(&self.walked_dirs),
+            linked_dirs: Arc::clone(&self.linked_dirs),
+        })
     }
+}
 
-    if verbose {
-        println!(
-            "Indexing complete: Found {} files, traversed {} directories, ignored {} items in {:.2?}",
-            files_discovered,
-            dirs_traversed,
-            *items_ignored.borrow(),
-            start_time.elapsed()
-        );
+// One per walker thread; buffers entries so the channel isn't hit for every file.
+struct BatchingVisitor {
+    batch: Vec<FileEntry>,
+    tx: SyncSender<Vec<FileEntry>>,
+    counters: Arc<WalkCounters>,
+    errors: Arc<Mutex<Vec<WalkError>>>,
+    follow_symlinks: bool,
+    // Whether to list the directories the walker descends into, for `WalkCounters::total_ignored`.
+    count_listed: bool,
+    max_depth: usize,
+    base_depth: usize,
+    // Directories already walked under another path; they're indexed again but not descended.
+    walked_dirs: Arc<HashSet<FileId>>,
+    // Symlinks to directories, with their depth below the root, for `walk_linked_dirs`.
+    linked_dirs: Arc<Mutex<Vec<(PathBuf, usize)>>>,
+}
+
+impl BatchingVisitor {
+    fn record_error(&self, error: WalkError) {
+        if let Ok(mut errors) = self.errors.lock() {
+            errors.push(error);
+        }
+    }
+
+    fn count_listed(&self, dir: &Path) {
+        if let Ok(children) = fs::read_dir(dir) {
+            self.counters
+                .listed
+                .fetch_add(children.count(), Ordering::Relaxed);
+        }
+    }
+
+    fn add_entry(&mut self, path: &Path) -> WalkState {
+        match read_entry(path) {
+            Some(file_entry) => {
+                self.batch.push(file_entry);
+                if self.batch.len() >= BATCH_SIZE {
+                    return self.flush();
+                }
+            }
+            None => self.record_error(non_utf8_error(path)),
+        }
+        WalkState::Continue
+    }
+
+    // A link that can't be followed because its target is missing is indexed like any other
+    // link. Anything else that stops it from being followed is an error.
+    fn queue_link(&mut self, path: &Path, depth: usize) {
+        match fs::metadata(path) {
+            Ok(metadata) if metadata.is_dir() => {
+                if let Ok(mut linked_dirs) = self.linked_dirs.lock() {
+                    linked_dirs.push((path.to_path_buf(), self.base_depth + depth));
+                }
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => self.record_error(WalkError {
+                path: path.to_string_lossy().into_owned(),
+                kind: e.kind().to_string(),
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    fn flush(&mut self) -> WalkState {
+        if self.batch.is_empty() {
+            return WalkState::Continue;
+        }
+        let batch = std::mem::replace(&mut self.batch, Vec::with_capacity(BATCH_SIZE));
+        match self.tx.send(batch) {
+            Ok(()) => WalkState::Continue,
+            Err(_) => WalkState::Quit, // The writer stopped on an error
+        }
+    }
+}
+
+impl ParallelVisitor for BatchingVisitor {
+    fn visit(&mut self, entry: Result<ignore::DirEntry, ignore::Error>) -> WalkState {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                self.record_error(walk_error(&err));
+                return WalkState::Continue;
+            }
+        };
+
+        // The walker lists a directory's entries unless it's a link (other than the one a walk
+        // starts from) or at the depth limit.
+        let descends = self.count_listed
+            && entry.file_type().is_some_and(|t| t.is_dir())
+            && self.base_depth + entry.depth() < self.max_depth;
+        if entry.depth() > 0 {
+            self.counters.visited.fetch_add(1, Ordering::Relaxed);
+        }
+
+        if entry.depth() == 0 && descends {
+            self.count_listed(entry.path());
+        }
+
+        // A linked directory's walk starts at the link, which the walk that found it already
+        // counted and indexed.
+        if entry.depth() == 0 && self.base_depth > 0 {
+            return WalkState::Continue;
+        }
+
+        let entry_path = entry.path();
+        let is_file = entry_path.is_file();
+        let is_dir = !is_file && entry_path.is_dir();
+        let is_symlink = entry.path_is_symlink();
+        if is_file {
+            self.counters.files.fetch_add(1, Ordering::Relaxed);
+        } else if is_dir {
+            self.counters.dirs.fetch_add(1, Ordering::Relaxed);
+        }
+
+        // The include root itself isn't indexed as an entry of its own.
+        if entry.depth() == 0 {
+            return WalkState::Continue;
+        }
+        if is_symlink && self.follow_symlinks {
+            self.queue_link(entry_path, entry.depth());
+        }
+        let walked_elsewhere = is_dir
+            && !is_symlink
+            && !self.walked_dirs.is_empty()
+            && file_id(entry_path).is_some_and(|id| self.walked_dirs.contains(&id));
+
+        // Broken symlinks are neither files nor directories, but they're indexed as links all the same.
+        let mut state = WalkState::Continue;
+        if is_file || is_dir || is_symlink {
+            state = self.add_entry(entry_path);
+        }
+        if walked_elsewhere && matches!(state, WalkState::Continue) {
+            state = WalkState::Skip;
+        }
+        if descends && entry.depth() > 0 && matches!(state, WalkState::Continue) {
+            self.count_listed(entry_path);
+        }
+        state
+    }
+}
+
+impl Drop for BatchingVisitor {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+// Reduces an error from the walker to the path it happened at and a short description of why.
+pub fn walk_error(err: &ignore::Error) -> WalkError {
+    let kind = match (err, err.io_error()) {
+        (_, Some(io_error)) => io_error.kind().to_string(),
+        (ignore::Error::Loop { .. }, None) => "filesystem loop".to_string(),
+        _ => "ignore file error".to_string(),
+    };
+    WalkError {
+        path: error_path(err).map_or_else(String::new, |p| p.to_string_lossy().into_owned()),
+        kind,
+        message: err.to_string(),
+    }
+}
+
+// Paths are stored as text, so one that isn't valid UTF-8 can't be indexed.
+pub fn non_utf8_error(path: &Path) -> WalkError {
+    WalkError {
+        path: path.to_string_lossy().into_owned(),
+        kind: "non-UTF-8 path".to_string(),
+        message: "The path isn't valid UTF-8".to_string(),
+    }
+}
+
+fn error_path(err: &ignore::Error) -> Option<&Path> {
+    match err {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::Loop { child, .. } => Some(child),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
+        }
+        ignore::Error::Partial(errs) => errs.iter().find_map(error_path),
+        _ => None,
+    }
+}
+
+// The walker doesn't follow links itself: its threads would race to decide which of several
+// paths to the same directory gets indexed, so the choice could change from run to run. Links
+// to directories are collected instead and walked here, one at a time in path order. A link is
+// only descended when its target isn't inside the root or a directory walked earlier, which also
+// stops cycles; a target that contains one of those has it indexed but not descended again.
+fn walk_linked_dirs(
+    root: &Path,
+    depth: usize,
+    visitor_builder: &mut BatchingVisitorBuilder,
+    walker_for: impl Fn(&Path, usize) -> WalkParallel,
+) {
+    let Ok(root) = fs::canonicalize(root) else {
+        return;
+    };
+    let mut walked_paths = vec![root.clone()];
+    let mut walked_ids: HashSet<FileId> = file_id(&root).into_iter().collect();
+
+    loop {
+        let mut linked_dirs = match visitor_builder.linked_dirs.lock() {
+            Ok(mut linked_dirs) => std::mem::take(&mut *linked_dirs),
+            Err(_) => return,
+        };
+        if linked_dirs.is_empty() {
+            return;
+        }
+        linked_dirs.sort();
+
+        for (link, link_depth) in linked_dirs {
+            let Ok(target) = fs::canonicalize(&link) else {
+                continue;
+            };
+            if link_depth >= depth || walked_paths.iter().any(|dir| target.starts_with(dir)) {
+                continue;
+            }
+            walked_ids.extend(file_id(&target));
+            walked_paths.push(target);
+
+            visitor_builder.base_depth = link_depth;
+            visitor_builder.walked_dirs = Arc::new(walked_ids.clone());
+            walker_for(&link, depth - link_depth).visit(visitor_builder);
+        }
+    }
+}
+
+// Size and mtime follow symlinks, so a link shows what it points to; a broken link has neither.
+pub fn read_entry(path: &Path) -> Option<FileEntry> {
+    let path_str = path.to_str()?;
+    let metadata = fs::metadata(path).ok();
+    let is_symlink = path
+        .symlink_metadata()
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false);
+
+    let kind = if is_symlink {
+        EntryKind::Symlink
+    } else if metadata.as_ref().is_some_and(|m| m.is_dir()) {
+        EntryKind::Dir
+    } else {
+        EntryKind::File
+    };
+    let mtime = metadata
+        .as_ref()
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64);
+
+    let link_target = if is_symlink {
+        fs::read_link(path)
+            .ok()
+            .map(|target| target.to_string_lossy().into_owned())
     } else {
-        println!("Indexing complete: Found {} files, traversed {} directories, ignored {} items in {:.2?}",
-            files_discovered, dirs_traversed, *items_ignored.borrow(), start_time.elapsed());
+        None
+    };
+
+    Some(FileEntry {
+        path: path_str.to_string(),
+        size: metadata.as_ref().map(|m| m.len()),
+        mtime,
+        kind: Some(kind),
+        extension: path
+            .extension()
+            .filter(|_| kind != EntryKind::Dir)
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase()),
+        link_target,
+    })
+}
+
+// Device and inode: the same directory reached through different paths has the same ID.
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+
+    fs::metadata(path).ok().map(|m| (m.dev(), m.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<FileId> {
+    None
+}
+
+// `config.ignore` globs are applied separately through `filter_entry`, on top of these settings.
+pub fn walk_builder(path: &Path, respect_ignore_files: bool) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(path);
+    builder.standard_filters(false);
+    if respect_ignore_files {
+        // Ignore files are honoured outside git repositories too, and ones in parent directories apply.
+        builder
+            .git_ignore(true)
+            .git_exclude(true)
+            .ignore(true)
+            .parents(true)
+            .require_git(false)
+            .add_custom_ignore_filename(IGNORE_FILENAME);
     }
+    builder
+}
 
-    Ok(())
+pub fn compile_ignore_patterns(config: &Config) -> Result<Vec<Pattern>> {
+    let patterns = config
+        .ignore
+        .iter()
+        .map(|s| Pattern::new(s))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(patterns)
+}
+
+pub fn is_ignored(ignore_patterns: &[Pattern], root: &Path, entry_path: &Path) -> bool {
+    // Check against absolute path
+    if ignore_patterns.iter().any(|p| p.matches_path(entry_path)) {
+        return true;
+    }
+
+    // Check against path relative to the provided path
+    if let Ok(relative_path) = entry_path.strip_prefix(root) {
+        if ignore_patterns
+            .iter()
+            .any(|p| p.matches_path(relative_path))
+        {
+            return true;
+        }
+    }
+
+    false
+}
+
+// Entries at or beneath a path the walk failed on are kept: a root that's missing or unreadable,
+// e.g. an unmounted drive, or a directory that couldn't be listed wasn't actually walked.
+fn prune_unseen(
+    conn: &Connection,
+    root: &str,
+    seen_paths: &HashSet<String>,
+    errors: &[WalkError],
+    verbose: bool,
+) -> Result<usize> {
+    // Match on the root followed by a separator so that pruning "/a/Doc" leaves "/a/Documents" alone.
+    let mut prefix = root.to_string();
+    if !prefix.ends_with(MAIN_SEPARATOR) {
+        prefix.push(MAIN_SEPARATOR);
+    }
+    let failed_paths: Vec<&Path> = errors
+        .iter()
+        .filter(|error| !error.path.is_empty())
+        .map(|error| Path::new(&error.path))
+        .collect();
+
+    let mut removed = 0;
+    for stale_path in db::paths_under(conn, &prefix)? {
+        if seen_paths.contains(&stale_path)
+            || failed_paths
+                .iter()
+                .any(|failed| Path::new(&stale_path).starts_with(failed))
+        {
+            continue;
+        }
+        removed += db::delete_file(conn, &stale_path)?;
+        if verbose {
+            println!("Removed: {}", stale_path);
+        }
+    }
+    Ok(removed)
+}
+
+// Removes entries whose paths are gone from disk, wherever they were indexed from. Paths that
+// can't be checked, e.g. for lack of permission, are kept. Returns how many were (or would be) removed.
+pub fn prune_missing(conn: &Connection, dry_run: bool, verbose: bool) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut removed = 0;
+    for path in db::all_paths(&tx)? {
+        // `symlink_metadata`, so a broken symlink still counts as existing.
+        let missing = matches!(
+            fs::symlink_metadata(&path),
+            Err(e) if e.kind() == io::ErrorKind::NotFound
+        );
+        if !missing {
+            continue;
+        }
+        if !dry_run {
+            db::delete_file(&tx, &path)?;
+        }
+        removed += 1;
+        if verbose {
+            println!("{}", path);
+        }
+    }
+    tx.commit()?;
+    Ok(removed)
 }
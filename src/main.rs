@@ -1,48 +1,344 @@
+mod clipboard;
 mod config;
 mod db;
 mod indexing;
+mod output;
+mod preview;
+mod query;
+mod rank;
+mod search_worker;
+mod shell;
+mod stats;
 mod tui;
+mod watch;
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use eyre::Result;
+use output::OutputFormat;
+use rusqlite::Connection;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 #[derive(Parser)]
-#[clap(author, version, about, long_about = None)]
+#[clap(author, version, about, long_about = None, args_conflicts_with_subcommands = true)]
 struct Cli {
-    /// The search term
-    search_term: Option<String>,
+    #[clap(subcommand)]
+    command: Option<Command>,
+
+    // `quickfind <term>` is short for `quickfind tui <term>`.
+    #[clap(flatten)]
+    tui: TuiArgs,
 
+    // `quickfind --index [--verbose]` from before subcommands existed, kept for scripts and cron jobs.
+    #[clap(long, short, action, hide = true, conflicts_with = "search_term")]
+    index: bool,
+
+    #[clap(long, short, action, hide = true, requires = "index")]
+    verbose: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
     /// Index files based on the configuration
+    Index(IndexArgs),
+    /// Print matching paths to stdout, for scripts
+    Search(SearchArgs),
+    /// Search interactively (the default)
+    Tui(TuiArgs),
+    /// Show what's in the index
+    Stats(StatsArgs),
+    /// List past index runs
+    History(HistoryArgs),
+    /// Remove entries whose paths no longer exist
+    Prune(PruneArgs),
+    /// Show or edit the configuration file
+    Config(ConfigArgs),
+    /// Show or maintain the index database
+    Db(DbArgs),
+    /// Print key bindings and a `qcd` function for your shell, e.g. `eval "$(quickfind init bash)"`
+    Init {
+        #[clap(value_enum)]
+        shell: shell::Shell,
+    },
+}
+
+#[derive(Args)]
+struct IndexArgs {
+    /// Index these paths instead of the configured `include` paths
+    paths: Vec<String>,
+
+    /// Keep the index up to date by watching the paths for changes after indexing them
     #[clap(long, short, action)]
-    index: bool,
+    watch: bool,
 
     /// Enable verbose output
     #[clap(long, short, action)]
     verbose: bool,
 }
 
-fn main() -> Result<()> {
+#[derive(Args)]
+struct SearchArgs {
+    /// The search term
+    search_term: String,
+
+    /// Separate printed paths with NUL instead of newline, for `xargs -0`
+    #[clap(long = "null", short = '0', action, conflicts_with = "format")]
+    null: bool,
+
+    /// Print at most this many paths
+    #[clap(long, short)]
+    limit: Option<usize>,
+
+    /// Treat the search term as a regular expression matched against the full path
+    #[clap(long, short, action)]
+    regex: bool,
+
+    /// Output format for printed results
+    #[clap(long, value_enum, default_value_t = OutputFormat::Plain)]
+    format: OutputFormat,
+}
+
+#[derive(Args)]
+struct TuiArgs {
+    /// The search term
+    search_term: Option<String>,
+
+    /// Treat the search term as a regular expression matched against the full path
+    #[clap(long, short, action)]
+    regex: bool,
+
+    /// Print the paths chosen in the interactive interface instead of opening them
+    #[clap(long, action)]
+    print_selection: bool,
+
+    /// Write the chosen paths to this file instead of stdout; implies --print-selection
+    #[clap(long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct StatsArgs {
+    /// Skip counting entries whose paths no longer exist, which checks every path on disk
+    #[clap(long, short, action)]
+    quick: bool,
+
+    /// List every path the latest index runs couldn't index, not just the first few
+    #[clap(long, action)]
+    errors: bool,
+}
+
+#[derive(Args)]
+struct HistoryArgs {
+    /// Only list runs that indexed this path
+    #[clap(long)]
+    root: Option<String>,
+
+    /// List at most this many runs, newest first
+    #[clap(long, short, default_value_t = 20)]
+    limit: usize,
+}
+
+#[derive(Args)]
+struct PruneArgs {
+    /// List what would be removed without removing it
+    #[clap(long, action)]
+    dry_run: bool,
+
+    /// Print every removed path
+    #[clap(long, short, action)]
+    verbose: bool,
+}
+
+#[derive(Args)]
+struct ConfigArgs {
+    /// Print only the path of the configuration file
+    #[clap(long, action, conflicts_with = "edit")]
+    path: bool,
+
+    /// Open the configuration file in your editor
+    #[clap(long, short, action)]
+    edit: bool,
+}
+
+#[derive(Args)]
+struct DbArgs {
+    /// Print only the path of the database file
+    #[clap(long, action, conflicts_with_all = ["vacuum", "check"])]
+    path: bool,
+
+    /// Rebuild the database file to reclaim unused space
+    #[clap(long, action)]
+    vacuum: bool,
+
+    /// Verify the integrity of the database and its search index
+    #[clap(long, action)]
+    check: bool,
+}
+
+// Exit codes follow grep: 0 when something matched, 1 when nothing did, 2 on errors.
+fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> Result<ExitCode> {
     let cli = Cli::parse();
+    let command = match cli.command {
+        Some(command) => command,
+        None if cli.index => {
+            eprintln!("`quickfind --index` is deprecated; use `quickfind index` instead.");
+            Command::Index(IndexArgs {
+                paths: Vec::new(),
+                watch: false,
+                verbose: cli.verbose,
+            })
+        }
+        None => Command::Tui(cli.tui),
+    };
+    // Before anything else, which could print to stdout or create files the shell doesn't expect.
+    if let Command::Init { shell } = command {
+        io::stdout().write_all(shell::init_script(shell).as_bytes())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
     let config = config::load_config()?;
     let conn = db::get_connection()?;
-    db::create_tables(&conn)?;
+    db::run_migrations(&conn)?;
 
-    if cli.index {
-        println!("Indexing files...");
-        let paths_to_index = if config.include.is_empty() {
-            vec![".".to_string()]
-        } else {
-            config.include.clone()
-        };
+    match command {
+        Command::Index(args) => run_index(&conn, &config, args),
+        Command::Search(args) => run_search(&conn, &config, args),
+        Command::Tui(args) => run_interactive(&conn, args),
+        Command::Stats(args) => stats::print_stats(&conn, &config, args.quick, args.errors),
+        Command::History(args) => stats::print_history(&conn, args.root.as_deref(), args.limit),
+        Command::Prune(args) => run_prune(&conn, args),
+        Command::Config(args) => run_config(&config, args),
+        Command::Db(args) => run_db(&conn, args),
+        Command::Init { .. } => unreachable!("handled above"),
+    }
+}
 
+fn run_index(conn: &Connection, config: &config::Config, args: IndexArgs) -> Result<ExitCode> {
+    let paths_to_index = if !args.paths.is_empty() {
+        args.paths
+    } else if config.include.is_empty() {
+        vec![".".to_string()]
+    } else {
+        config.include.clone()
+    };
+
+    if args.watch {
+        watch::watch_files(conn, config, &paths_to_index, args.verbose)?;
+    } else {
+        println!("Indexing files...");
         for path in paths_to_index {
             println!("Indexing path: {}", path);
-            indexing::index_files(&conn, &config, &path, cli.verbose)?;
+            indexing::index_files(conn, config, &path, args.verbose)?;
         }
         println!("Indexing complete.");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_search(conn: &Connection, config: &config::Config, args: SearchArgs) -> Result<ExitCode> {
+    let search_options = db::SearchOptions {
+        include_dirs: config.search_dirs,
+        mode: if args.regex {
+            db::MatchMode::Regex
+        } else {
+            config.match_mode
+        },
+    };
+    let mut results: Vec<_> = db::search_files(conn, &args.search_term, &search_options)?
+        .into_iter()
+        .map(|result| result.entry)
+        .collect();
+    if let Some(limit) = args.limit {
+        results.truncate(limit);
+    }
+    output::print_results(&results, args.format, args.null)?;
+    if results.is_empty() {
+        return Ok(ExitCode::from(1));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_interactive(conn: &Connection, args: TuiArgs) -> Result<ExitCode> {
+    let print_selection = args.print_selection || args.output.is_some();
+    let chosen = tui::run_tui(conn, args.search_term, args.regex, print_selection)?;
+    // Written only now that the interface is gone, so the paths land where the shell expects them.
+    let text: String = chosen.iter().map(|path| format!("{}\n", path)).collect();
+    match &args.output {
+        Some(output) => fs::write(output, text)?,
+        None => io::stdout().write_all(text.as_bytes())?,
+    }
+    if print_selection && chosen.is_empty() {
+        return Ok(ExitCode::from(1));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_prune(conn: &Connection, args: PruneArgs) -> Result<ExitCode> {
+    let removed = indexing::prune_missing(conn, args.dry_run, args.verbose || args.dry_run)?;
+    if args.dry_run {
+        println!("{} entries would be removed.", removed);
     } else {
-        tui::run_tui(&conn, cli.search_term)?;
+        println!("Removed {} entries.", removed);
     }
+    Ok(ExitCode::SUCCESS)
+}
 
-    Ok(())
+fn run_config(config: &config::Config, args: ConfigArgs) -> Result<ExitCode> {
+    let config_path = config::get_config_path()?;
+    if args.path {
+        println!("{}", config_path.display());
+    } else if args.edit {
+        let path = config_path.to_string_lossy().into_owned();
+        tui::open_file_with_editor(&[path], config.editor.clone())?;
+    } else {
+        println!("# {}", config_path.display());
+        print!("{}", fs::read_to_string(&config_path)?);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn run_db(conn: &Connection, args: DbArgs) -> Result<ExitCode> {
+    let db_path = db::get_db_path()?;
+    if args.path {
+        println!("{}", db_path.display());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.vacuum {
+        let before = fs::metadata(&db_path)?.len();
+        db::vacuum(conn)?;
+        let after = fs::metadata(&db_path)?.len();
+        println!("Vacuumed the database: {} -> {} bytes.", before, after);
+    }
+    if args.check {
+        let problems = db::check_integrity(conn)?;
+        if !problems.is_empty() {
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            eyre::bail!(
+                "The database failed its integrity check; delete {} and run `quickfind index` to rebuild it",
+                db_path.display()
+            );
+        }
+        println!("The database is intact.");
+    }
+    if !args.vacuum && !args.check {
+        println!("Path:           {}", db_path.display());
+        println!("Schema version: {}", db::schema_version(conn)?);
+        println!("Size:           {} bytes", fs::metadata(&db_path)?.len());
+    }
+    Ok(ExitCode::SUCCESS)
 }
@@ -0,0 +1,183 @@
+use crate::db::FileEntry;
+use chrono::{Local, TimeZone};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// Bumped whenever a field is renamed, removed or changes meaning. Adding fields doesn't bump it.
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Plain,
+    Json,
+    Ndjson,
+    Csv,
+}
+
+#[derive(Serialize)]
+struct ResultRecord<'a> {
+    path: &'a str,
+    name: Option<&'a str>,
+    parent: Option<&'a str>,
+    kind: Option<&'static str>,
+    size: Option<u64>,
+    mtime: Option<i64>,
+    extension: Option<&'a str>,
+    link_target: Option<&'a str>,
+}
+
+impl<'a> ResultRecord<'a> {
+    fn new(entry: &'a FileEntry) -> Self {
+        let path = Path::new(&entry.path);
+        Self {
+            path: &entry.path,
+            name: path.file_name().and_then(|n| n.to_str()),
+            parent: path.parent().and_then(|p| p.to_str()),
+            kind: entry.kind.map(|k| k.as_str()),
+            size: entry.size,
+            mtime: entry.mtime,
+            extension: entry.extension.as_deref(),
+            link_target: entry.link_target.as_deref(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonDocument<'a> {
+    version: u32,
+    results: Vec<ResultRecord<'a>>,
+}
+
+pub fn print_results(
+    results: &[FileEntry],
+    format: OutputFormat,
+    null_separated: bool,
+) -> io::Result<()> {
+    ignore_broken_pipe(write_to_stdout(|out| match format {
+        OutputFormat::Plain => write_plain(out, results, null_separated),
+        OutputFormat::Json => write_json(out, results),
+        OutputFormat::Ndjson => write_ndjson(out, results),
+        OutputFormat::Csv => write_csv(out, results),
+    }))
+}
+
+fn write_plain(out: &mut dyn Write, results: &[FileEntry], null_separated: bool) -> io::Result<()> {
+    let separator = if null_separated { b'\0' } else { b'\n' };
+    for entry in results {
+        out.write_all(entry.path.as_bytes())?;
+        out.write_all(&[separator])?;
+    }
+    Ok(())
+}
+
+fn write_json(out: &mut dyn Write, results: &[FileEntry]) -> io::Result<()> {
+    let document = JsonDocument {
+        version: FORMAT_VERSION,
+        results: results.iter().map(ResultRecord::new).collect(),
+    };
+    serde_json::to_writer_pretty(&mut *out, &document)?;
+    writeln!(out)
+}
+
+fn write_ndjson(out: &mut dyn Write, results: &[FileEntry]) -> io::Result<()> {
+    for entry in results {
+        serde_json::to_writer(&mut *out, &ResultRecord::new(entry))?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+fn write_csv(out: &mut dyn Write, results: &[FileEntry]) -> io::Result<()> {
+    writeln!(
+        out,
+        "path,name,parent,kind,size,mtime,extension,link_target"
+    )?;
+    for entry in results {
+        let record = ResultRecord::new(entry);
+        let fields = [
+            Some(record.path.to_string()),
+            record.name.map(str::to_string),
+            record.parent.map(str::to_string),
+            record.kind.map(str::to_string),
+            record.size.map(|s| s.to_string()),
+            record.mtime.map(|m| m.to_string()),
+            record.extension.map(str::to_string),
+            record.link_target.map(str::to_string),
+        ];
+        let line = fields
+            .iter()
+            .map(|field| csv_field(field.as_deref().unwrap_or("")))
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+// Quotes a field as RFC 4180 requires: when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_to_stdout<F>(write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> io::Result<()>,
+{
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    write(&mut out)?;
+    out.flush()
+}
+
+// A reader like `head` closing the pipe early isn't an error for us.
+fn ignore_broken_pipe(result: io::Result<()>) -> io::Result<()> {
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        other => other,
+    }
+}
+
+pub fn format_size(size: Option<u64>) -> String {
+    let Some(size) = size else {
+        return "-".to_string();
+    };
+    let units = ["B", "K", "M", "G", "T"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}{}", size, units[0])
+    } else {
+        format!("{:.1}{}", value, units[unit])
+    }
+}
+
+pub fn format_mtime(mtime: Option<i64>) -> String {
+    mtime
+        .and_then(|secs| Local.timestamp_opt(secs, 0).single())
+        .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+// A rough, human-readable length of time, e.g. "3 days".
+pub fn format_age(seconds: i64) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    let (count, unit) = match seconds.max(0) {
+        s if s < MINUTE => (s, "second"),
+        s if s < HOUR => (s / MINUTE, "minute"),
+        s if s < DAY => (s / HOUR, "hour"),
+        s => (s / DAY, "day"),
+    };
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
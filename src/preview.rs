@@ -0,0 +1,204 @@
+use std::fs::{self, File, Metadata};
+use std::io::Read;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+const MAX_PREVIEW_BYTES: u64 = 64 * 1024; // Read from the start of a text file
+const MAX_PREVIEW_LINES: usize = 200;
+const MAX_PREVIEW_ENTRIES: usize = 200; // Listed for a directory
+const TAB_WIDTH: usize = 4;
+
+pub enum Preview {
+    Text {
+        lines: Vec<String>,
+        truncated: bool,
+    },
+    Directory {
+        entries: Vec<String>, // Subdirectories end with '/'
+        total: usize,
+    },
+    Binary {
+        size: u64,
+        kind: String,
+        permissions: String,
+    },
+    Unavailable(String),
+}
+
+// Loads previews on a thread so that reading a large file or a slow directory never stalls
+// scrolling. Only the newest request is loaded; ones queued behind it are skipped.
+pub struct PreviewLoader {
+    requests: Sender<String>,
+    previews: Receiver<(String, Preview)>,
+}
+
+impl PreviewLoader {
+    pub fn spawn() -> Self {
+        let (requests, request_rx) = mpsc::channel::<String>();
+        let (preview_tx, previews) = mpsc::channel();
+
+        thread::spawn(move || {
+            while let Ok(mut path) = request_rx.recv() {
+                while let Ok(newer) = request_rx.try_recv() {
+                    path = newer;
+                }
+                let preview = load_preview(Path::new(&path));
+                if preview_tx.send((path, preview)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self { requests, previews }
+    }
+
+    pub fn request(&self, path: &str) {
+        // A loader that has gone away just never answers, which leaves the pane empty.
+        let _ = self.requests.send(path.to_string());
+    }
+
+    // Returns the newest preview loaded so far, with the path it belongs to.
+    pub fn try_recv(&self) -> Option<(String, Preview)> {
+        self.previews.try_iter().last()
+    }
+}
+
+fn load_preview(path: &Path) -> Preview {
+    // Follows symlinks, so a link previews as whatever it points to.
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => return Preview::Unavailable(e.to_string()),
+    };
+    let result = if metadata.is_dir() {
+        load_directory(path)
+    } else {
+        load_file(path, &metadata)
+    };
+    result.unwrap_or_else(|e| Preview::Unavailable(e.to_string()))
+}
+
+fn load_directory(path: &Path) -> std::io::Result<Preview> {
+    let mut entries = Vec::new();
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        total += 1;
+        // Counting the rest is cheap, but sorting a huge directory is not worth it for a preview.
+        if entries.len() < MAX_PREVIEW_ENTRIES {
+            let mut name = entry.file_name().to_string_lossy().into_owned();
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                name.push('/');
+            }
+            entries.push(name);
+        }
+    }
+    entries.sort();
+    Ok(Preview::Directory { entries, total })
+}
+
+fn load_file(path: &Path, metadata: &Metadata) -> std::io::Result<Preview> {
+    let mut bytes = Vec::new();
+    File::open(path)?
+        .take(MAX_PREVIEW_BYTES)
+        .read_to_end(&mut bytes)?;
+
+    let text = match std::str::from_utf8(&bytes) {
+        Ok(text) => text,
+        // The cap may have cut a character in half; anything else invalid is binary.
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return Ok(binary_preview(&bytes, metadata)),
+    };
+    if text.contains('\0') {
+        return Ok(binary_preview(&bytes, metadata));
+    }
+
+    let mut lines: Vec<String> = text
+        .lines()
+        .take(MAX_PREVIEW_LINES)
+        .map(clean_line)
+        .collect();
+    let cut_short = metadata.len() > MAX_PREVIEW_BYTES;
+    if cut_short && lines.len() < MAX_PREVIEW_LINES {
+        // The last line is probably incomplete.
+        lines.pop();
+    }
+    let truncated = cut_short || text.lines().nth(MAX_PREVIEW_LINES).is_some();
+    Ok(Preview::Text { lines, truncated })
+}
+
+// Tabs and control characters would throw off the terminal's idea of where the text is.
+fn clean_line(line: &str) -> String {
+    let mut cleaned = String::with_capacity(line.len());
+    for c in line.chars() {
+        match c {
+            '\t' => {
+                let spaces = TAB_WIDTH - cleaned.chars().count() % TAB_WIDTH;
+                cleaned.extend(std::iter::repeat_n(' ', spaces));
+            }
+            c if c.is_control() => cleaned.push('?'),
+            c => cleaned.push(c),
+        }
+    }
+    cleaned
+}
+
+fn binary_preview(bytes: &[u8], metadata: &Metadata) -> Preview {
+    Preview::Binary {
+        size: metadata.len(),
+        kind: file_kind(bytes).to_string(),
+        permissions: format_permissions(metadata),
+    }
+}
+
+// Recognizes a few common formats by their leading bytes.
+fn file_kind(bytes: &[u8]) -> &'static str {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x7fELF", "ELF executable"),
+        (b"MZ", "Windows executable"),
+        (b"\x89PNG", "PNG image"),
+        (b"\xff\xd8\xff", "JPEG image"),
+        (b"GIF8", "GIF image"),
+        (b"%PDF", "PDF document"),
+        (b"PK\x03\x04", "Zip archive"),
+        (b"\x1f\x8b", "gzip archive"),
+        (b"BZh", "bzip2 archive"),
+        (b"\xfd7zXZ\x00", "xz archive"),
+        (b"7z\xbc\xaf\x27\x1c", "7-Zip archive"),
+        (b"\x28\xb5\x2f\xfd", "Zstandard archive"),
+        (b"SQLite format 3\0", "SQLite database"),
+        (b"OggS", "Ogg media"),
+        (b"fLaC", "FLAC audio"),
+        (b"ID3", "MP3 audio"),
+    ];
+    SIGNATURES
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+        .map_or("binary data", |(_, kind)| kind)
+}
+
+#[cfg(unix)]
+fn format_permissions(metadata: &Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode();
+    let mut permissions = String::with_capacity(9);
+    for shift in [6, 3, 0] {
+        let bits = mode >> shift;
+        permissions.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        permissions.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        permissions.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    format!("{} ({:o})", permissions, mode & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn format_permissions(metadata: &Metadata) -> String {
+    if metadata.permissions().readonly() {
+        "read-only".to_string()
+    } else {
+        "read-write".to_string()
+    }
+}
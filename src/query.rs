@@ -0,0 +1,847 @@
+use crate::db::{EntryKind, MatchMode};
+use chrono::{Local, NaiveDate, TimeZone};
+use eyre::{bail, eyre, Result};
+use rusqlite::types::Value;
+use std::path::MAIN_SEPARATOR;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const FIELDS: &[&str] = &[
+    "ext", "dir", "name", "size", "modified", "mtime", "type", "kind",
+];
+
+// A parsed search such as `report ext:pdf -draft modified:<30d`.
+// Whitespace-separated terms must all match; `OR` binds looser than that, and parentheses group.
+#[derive(Debug, Clone)]
+pub struct Query {
+    expr: Option<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    // A word that may contain `*` and `?` wildcards.
+    Text(String),
+    // A quoted phrase, matched literally.
+    Phrase(String),
+    // A word starting with '.', matched against the end of the path (e.g. '.mp3', '.config').
+    Suffix(String),
+    Extension(Vec<String>),
+    Dir(String),
+    Name(String),
+    Size(Comparison, u64),
+    // Half-open range of modification times, [after, before).
+    Modified {
+        after: Option<i64>,
+        before: Option<i64>,
+    },
+    Kind(EntryKind),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+}
+
+impl Comparison {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Equal => "=",
+        }
+    }
+
+    fn split(value: &str) -> (Comparison, &str) {
+        for (prefix, comparison) in [
+            (">=", Comparison::GreaterOrEqual),
+            ("<=", Comparison::LessOrEqual),
+            (">", Comparison::Greater),
+            ("<", Comparison::Less),
+            ("=", Comparison::Equal),
+        ] {
+            if let Some(rest) = value.strip_prefix(prefix) {
+                return (comparison, rest);
+            }
+        }
+        (Comparison::Equal, value)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word { text: String, quoted: bool },
+    Not,
+    Or,
+    LeftParen,
+    RightParen,
+}
+
+pub fn parse(input: &str) -> Result<Query> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Ok(Query { expr: None });
+    }
+
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.parse_or()?;
+    if parser.pos < parser.tokens.len() {
+        bail!("Unexpected ')' without a matching '('");
+    }
+    Ok(Query { expr: Some(expr) })
+}
+
+impl Query {
+    pub fn is_empty(&self) -> bool {
+        self.expr.is_none()
+    }
+
+    // The words results should be ranked and highlighted by: every text term that isn't negated.
+    pub fn text_terms(&self) -> Vec<String> {
+        let mut terms = Vec::new();
+        if let Some(expr) = &self.expr {
+            collect_text_terms(expr, &mut terms);
+        }
+        terms
+    }
+
+    // Compiles to a condition for a WHERE clause over the `files` table, with `?` placeholders
+    // matching the returned parameters in order.
+    pub fn to_sql(&self, mode: MatchMode) -> (String, Vec<Value>) {
+        let mut params = Vec::new();
+        let sql = match &self.expr {
+            Some(expr) => expr_to_sql(expr, mode, &mut params),
+            None => "0".to_string(),
+        };
+        (sql, params)
+    }
+}
+
+fn collect_text_terms(expr: &Expr, terms: &mut Vec<String>) {
+    match expr {
+        Expr::And(items) | Expr::Or(items) => {
+            for item in items {
+                collect_text_terms(item, terms);
+            }
+        }
+        Expr::Not(_) => {}
+        Expr::Term(
+            Term::Text(word) | Term::Phrase(word) | Term::Suffix(word) | Term::Name(word),
+        ) => terms.push(word.clone()),
+        Expr::Term(_) => {}
+    }
+}
+
+fn expr_to_sql(expr: &Expr, mode: MatchMode, params: &mut Vec<Value>) -> String {
+    match expr {
+        Expr::And(items) => join_sql(items, " AND ", mode, params),
+        Expr::Or(items) => join_sql(items, " OR ", mode, params),
+        // Metadata comparisons are NULL for rows indexed before metadata existed; treat that as no match.
+        Expr::Not(inner) => format!("NOT COALESCE({}, 0)", expr_to_sql(inner, mode, params)),
+        Expr::Term(term) => term_to_sql(term, mode, params),
+    }
+}
+
+fn join_sql(items: &[Expr], separator: &str, mode: MatchMode, params: &mut Vec<Value>) -> String {
+    let parts: Vec<String> = items
+        .iter()
+        .map(|item| expr_to_sql(item, mode, params))
+        .collect();
+    format!("({})", parts.join(separator))
+}
+
+// The trigram index narrows down candidate rows; the term's own condition still decides the match.
+// Placeholders are numbered in order of appearance, so the condition's parameters are bound first.
+fn term_to_sql(term: &Term, mode: MatchMode, params: &mut Vec<Value>) -> String {
+    let condition = term_condition(term, mode, params);
+    match trigram_query(term, mode) {
+        Some(fts_query) => {
+            params.push(Value::Text(fts_query));
+            format!(
+                "({} AND id IN (SELECT rowid FROM files_fts WHERE files_fts MATCH ? \
+                 UNION ALL SELECT id FROM files_fts_pending))",
+                condition
+            )
+        }
+        None => condition,
+    }
+}
+
+// An FTS5 query requiring every literal piece of the term, e.g. `main*rs` becomes `"main"`.
+// Trigrams can't find pieces shorter than three characters, and fuzzy words aren't contiguous.
+fn trigram_query(term: &Term, mode: MatchMode) -> Option<String> {
+    let text = match term {
+        Term::Text(word) if mode != MatchMode::Fuzzy => word,
+        Term::Phrase(word) | Term::Suffix(word) | Term::Dir(word) | Term::Name(word) => word,
+        _ => return None,
+    };
+    let phrases: Vec<String> = text
+        .split(['*', '?'])
+        .filter(|piece| piece.chars().count() >= 3)
+        .map(|piece| format!("\"{}\"", piece.replace('"', "\"\"")))
+        .collect();
+    if phrases.is_empty() {
+        None
+    } else {
+        Some(phrases.join(" AND "))
+    }
+}
+
+fn term_condition(term: &Term, mode: MatchMode, params: &mut Vec<Value>) -> String {
+    let dir_part = format!("rtrim(path, replace(path, '{0}', ''))", MAIN_SEPARATOR);
+    let mut bind = |value: Value| {
+        params.push(value);
+        "?".to_string()
+    };
+
+    match term {
+        Term::Text(word) => {
+            let pattern = match mode {
+                // Regex queries bypass the query syntax in `db::search_files`.
+                MatchMode::Substring | MatchMode::Regex => {
+                    format!("%{}%", like_pattern(&word.to_lowercase()))
+                }
+                MatchMode::Fuzzy => fuzzy_pattern(&word.to_lowercase()),
+            };
+            format!(
+                "LOWER(path) LIKE {} ESCAPE '\\'",
+                bind(Value::Text(pattern))
+            )
+        }
+        Term::Phrase(phrase) => {
+            let pattern = format!("%{}%", escape_like(&phrase.to_lowercase()));
+            format!(
+                "LOWER(path) LIKE {} ESCAPE '\\'",
+                bind(Value::Text(pattern))
+            )
+        }
+        Term::Suffix(word) => {
+            let pattern = format!("%{}", like_pattern(word));
+            format!("path LIKE {} ESCAPE '\\'", bind(Value::Text(pattern)))
+        }
+        Term::Extension(extensions) => {
+            let placeholders: Vec<String> = extensions
+                .iter()
+                .map(|ext| bind(Value::Text(ext.clone())))
+                .collect();
+            format!("extension IN ({})", placeholders.join(", "))
+        }
+        Term::Dir(word) => {
+            let pattern = format!("%{}%", like_pattern(&word.to_lowercase()));
+            format!(
+                "LOWER({}) LIKE {} ESCAPE '\\'",
+                dir_part,
+                bind(Value::Text(pattern))
+            )
+        }
+        Term::Name(word) => {
+            let pattern = format!("%{}%", like_pattern(&word.to_lowercase()));
+            format!(
+                "LOWER(substr(path, length({}) + 1)) LIKE {} ESCAPE '\\'",
+                dir_part,
+                bind(Value::Text(pattern))
+            )
+        }
+        Term::Size(comparison, bytes) => {
+            format!(
+                "size {} {}",
+                comparison.as_sql(),
+                bind(Value::Integer(*bytes as i64))
+            )
+        }
+        Term::Modified { after, before } => {
+            let mut conditions = Vec::new();
+            if let Some(after) = after {
+                conditions.push(format!("mtime >= {}", bind(Value::Integer(*after))));
+            }
+            if let Some(before) = before {
+                conditions.push(format!("mtime < {}", bind(Value::Integer(*before))));
+            }
+            format!("({})", conditions.join(" AND "))
+        }
+        // Rows indexed before `kind` existed are NULL and are always files.
+        Term::Kind(EntryKind::File) => "(kind = 'file' OR kind IS NULL)".to_string(),
+        Term::Kind(kind) => format!("kind = {}", bind(Value::Text(kind.as_str().to_string()))),
+    }
+}
+
+// `*` and `?` are the user-facing wildcards; SQL's own wildcards are matched literally.
+fn like_pattern(word: &str) -> String {
+    escape_like(word).replace('*', "%").replace('?', "_")
+}
+
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Narrows candidates to paths containing the word as a subsequence, e.g. 'qfcfg' becomes
+// '%q%f%c%f%g%'. Scoring happens afterwards in `rank`. A subsequence already allows anything
+// between its characters, so wildcards are dropped rather than matched literally.
+fn fuzzy_pattern(word: &str) -> String {
+    let mut pattern = String::from("%");
+    for c in word.chars().filter(|c| !matches!(c, '*' | '?')) {
+        pattern.push_str(&escape_like(&c.to_string()));
+        pattern.push('%');
+    }
+    pattern
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LeftParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RightParen);
+            }
+            '-' | '!' => {
+                chars.next();
+                match chars.peek() {
+                    // A lone '-' is just something to search for.
+                    None => tokens.push(word_token(c.to_string(), false)),
+                    Some(next) if next.is_whitespace() => {
+                        tokens.push(word_token(c.to_string(), false))
+                    }
+                    Some(_) => tokens.push(Token::Not),
+                }
+            }
+            '"' => {
+                chars.next();
+                let phrase = read_quoted(&mut chars)?;
+                tokens.push(word_token(phrase, true));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == ')' {
+                        break;
+                    }
+                    chars.next();
+                    // Allows quoting a field value, e.g. name:"my notes".
+                    if c == '"' && word.ends_with(':') {
+                        word.push_str(&read_quoted(&mut chars)?);
+                    } else {
+                        word.push(c);
+                    }
+                }
+                if word == "OR" {
+                    tokens.push(Token::Or);
+                } else {
+                    tokens.push(word_token(word, false));
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn word_token(text: String, quoted: bool) -> Token {
+    Token::Word { text, quoted }
+}
+
+fn read_quoted(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String> {
+    let mut text = String::new();
+    for c in chars.by_ref() {
+        if c == '"' {
+            return Ok(text);
+        }
+        text.push(c);
+    }
+    bail!("Missing closing quote after \"{}", text)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut branches = vec![self.parse_and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            branches.push(self.parse_and()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.remove(0)
+        } else {
+            Expr::Or(branches)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut items = Vec::new();
+        while let Some(token) = self.peek() {
+            if matches!(token, Token::Or | Token::RightParen) {
+                break;
+            }
+            items.push(self.parse_unary()?);
+        }
+        match items.len() {
+            0 => match (
+                self.peek(),
+                self.pos.checked_sub(1).map(|i| &self.tokens[i]),
+            ) {
+                (Some(Token::RightParen), Some(Token::LeftParen)) => bail!("Empty parentheses"),
+                (Some(Token::RightParen), _) => bail!("Unexpected ')' without a matching '('"),
+                (None, Some(Token::LeftParen)) => bail!("Missing closing ')'"),
+                _ => bail!("'OR' needs a search term on both sides"),
+            },
+            1 => Ok(items.remove(0)),
+            _ => Ok(Expr::And(items)),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            if matches!(self.peek(), None | Some(Token::Or | Token::RightParen)) {
+                bail!("'-' and '!' must be followed by something to exclude");
+            }
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_atom()
+    }
+
+    fn parse_atom(&mut self) -> Result<Expr> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| eyre!("Incomplete query"))?;
+        self.pos += 1;
+        match token {
+            Token::LeftParen => {
+                let inner = self.parse_or()?;
+                if self.peek() != Some(&Token::RightParen) {
+                    bail!("Missing closing ')'");
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Token::Word { text, quoted: true } => Ok(Expr::Term(Term::Phrase(text.clone()))),
+            Token::Word {
+                text,
+                quoted: false,
+            } => Ok(Expr::Term(parse_term(text)?)),
+            Token::RightParen => bail!("Unexpected ')' without a matching '('"),
+            Token::Or => bail!("'OR' needs a search term on both sides"),
+            Token::Not => bail!("'-' and '!' must be followed by something to exclude"),
+        }
+    }
+}
+
+fn parse_term(word: &str) -> Result<Term> {
+    let Some((field, value)) = word.split_once(':') else {
+        return Ok(if word.starts_with('.') {
+            Term::Suffix(word.to_string())
+        } else {
+            Term::Text(word.to_string())
+        });
+    };
+
+    match field.to_lowercase().as_str() {
+        name if value.is_empty() && FIELDS.contains(&name) => {
+            bail!("Missing value after '{}:'", field)
+        }
+        "ext" => Ok(Term::Extension(
+            value
+                .split(',')
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect(),
+        )),
+        "dir" => Ok(Term::Dir(value.to_string())),
+        "name" => Ok(Term::Name(value.to_string())),
+        "size" => {
+            let (comparison, amount) = Comparison::split(value);
+            let bytes = parse_size(amount)
+                .ok_or_else(|| eyre!("Invalid size '{}', expected e.g. size:>10M", value))?;
+            Ok(Term::Size(comparison, bytes))
+        }
+        "modified" | "mtime" => parse_modified(value),
+        "type" | "kind" => match value.to_lowercase().as_str() {
+            "file" | "f" => Ok(Term::Kind(EntryKind::File)),
+            "dir" | "d" | "directory" => Ok(Term::Kind(EntryKind::Dir)),
+            "symlink" | "l" | "link" => Ok(Term::Kind(EntryKind::Symlink)),
+            _ => bail!("Unknown type '{}', expected file, dir or symlink", value),
+        },
+        // Anything else before a ':' is part of what's searched for, e.g. `std::fs` or `notes:todo`.
+        _ => Ok(Term::Text(word.to_string())),
+    }
+}
+
+// Binary units, e.g. "10M" is 10 * 1024 * 1024 bytes. A bare number is bytes.
+fn parse_size(value: &str) -> Option<u64> {
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+// Either an age ("<7d" is newer than a week, ">1y" is older than a year) or a date
+// ("2024-05-01", ">=2024-05-01"). A bare age means "within".
+fn parse_modified(value: &str) -> Result<Term> {
+    let (comparison, amount) = Comparison::split(value);
+
+    if let Ok(date) = NaiveDate::parse_from_str(amount, "%Y-%m-%d") {
+        let day_start = local_timestamp(date)?;
+        let next_day_start = local_timestamp(date.succ_opt().unwrap_or(date))?;
+        let (after, before) = match comparison {
+            Comparison::Less => (None, Some(day_start)),
+            Comparison::LessOrEqual => (None, Some(next_day_start)),
+            Comparison::Greater => (Some(next_day_start), None),
+            Comparison::GreaterOrEqual => (Some(day_start), None),
+            Comparison::Equal => (Some(day_start), Some(next_day_start)),
+        };
+        return Ok(Term::Modified { after, before });
+    }
+
+    let age = parse_age(amount).ok_or_else(|| {
+        eyre!(
+            "Invalid time '{}', expected an age like modified:<7d or a date like modified:>2024-05-01",
+            value
+        )
+    })?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let threshold = now - age;
+    Ok(match comparison {
+        Comparison::Greater | Comparison::GreaterOrEqual => Term::Modified {
+            after: None,
+            before: Some(threshold),
+        },
+        _ => Term::Modified {
+            after: Some(threshold),
+            before: None,
+        },
+    })
+}
+
+// Seconds in an age such as "30m", "12h", "7d", "2w", "6mo" or "1y".
+fn parse_age(value: &str) -> Option<i64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: i64 = number.parse().ok()?;
+    let unit_seconds = match unit.to_lowercase().as_str() {
+        "s" => 1,
+        "m" | "min" => 60,
+        "h" => 60 * 60,
+        "" | "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "mo" => 30 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        _ => return None,
+    };
+    number.checked_mul(unit_seconds)
+}
+
+fn local_timestamp(date: NaiveDate) -> Result<i64> {
+    let midnight = date.and_hms_opt(0, 0, 0).unwrap_or_default();
+    Local
+        .from_local_datetime(&midnight)
+        .earliest()
+        .map(|t| t.timestamp())
+        .ok_or_else(|| eyre!("{} has no midnight in the local time zone", date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str) -> Token {
+        word_token(text.to_string(), false)
+    }
+
+    fn text(word: &str) -> Expr {
+        Expr::Term(Term::Text(word.to_string()))
+    }
+
+    fn parse_expr(input: &str) -> Expr {
+        parse(input).unwrap().expr.unwrap()
+    }
+
+    fn parse_term_of(input: &str) -> Term {
+        match parse_expr(input) {
+            Expr::Term(term) => term,
+            expr => panic!("{} parsed to {:?}, not a single term", input, expr),
+        }
+    }
+
+    fn parse_error(input: &str) -> String {
+        parse(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn tokenizes_words_operators_and_parentheses() {
+        assert_eq!(
+            tokenize("(a OR b) -c !d").unwrap(),
+            [
+                Token::LeftParen,
+                word("a"),
+                Token::Or,
+                word("b"),
+                Token::RightParen,
+                Token::Not,
+                word("c"),
+                Token::Not,
+                word("d"),
+            ]
+        );
+        // Only an uppercase OR is an operator, and a lone '-' is a word.
+        assert_eq!(
+            tokenize("or - x-y").unwrap(),
+            [word("or"), word("-"), word("x-y")]
+        );
+    }
+
+    #[test]
+    fn tokenizes_quotes() {
+        assert_eq!(
+            tokenize(r#""my notes" name:"a b""#).unwrap(),
+            [word_token("my notes".to_string(), true), word("name:a b")]
+        );
+        assert!(tokenize(r#""unclosed"#).is_err());
+    }
+
+    #[test]
+    fn empty_input_is_an_empty_query() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_eq!(
+            parse_expr("a b OR c"),
+            Expr::Or(vec![Expr::And(vec![text("a"), text("b")]), text("c")])
+        );
+        assert_eq!(
+            parse_expr("a (b OR c)"),
+            Expr::And(vec![text("a"), Expr::Or(vec![text("b"), text("c")])])
+        );
+    }
+
+    #[test]
+    fn negation_applies_to_the_next_term_or_group() {
+        assert_eq!(
+            parse_expr("a -b !c"),
+            Expr::And(vec![
+                text("a"),
+                Expr::Not(Box::new(text("b"))),
+                Expr::Not(Box::new(text("c"))),
+            ])
+        );
+        assert_eq!(
+            parse_expr("-(a OR b)"),
+            Expr::Not(Box::new(Expr::Or(vec![text("a"), text("b")])))
+        );
+        assert_eq!(
+            parse_error("(a -)"),
+            "'-' and '!' must be followed by something to exclude"
+        );
+    }
+
+    #[test]
+    fn negated_terms_are_not_highlighted() {
+        assert_eq!(
+            parse("a -b (c OR d)").unwrap().text_terms(),
+            ["a", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn reports_unbalanced_parentheses_and_operators() {
+        assert_eq!(parse_error("("), "Missing closing ')'");
+        assert_eq!(
+            parse_error("(jpg OR"),
+            "'OR' needs a search term on both sides"
+        );
+        assert_eq!(parse_error("(jpg OR png"), "Missing closing ')'");
+        assert_eq!(parse_error("a)"), "Unexpected ')' without a matching '('");
+        assert_eq!(parse_error("()"), "Empty parentheses");
+        assert_eq!(
+            parse_error("OR a"),
+            "'OR' needs a search term on both sides"
+        );
+    }
+
+    #[test]
+    fn parses_plain_words_and_suffixes() {
+        assert_eq!(parse_term_of("main*rs"), Term::Text("main*rs".to_string()));
+        assert_eq!(parse_term_of(".mp3"), Term::Suffix(".mp3".to_string()));
+        assert_eq!(parse_term_of("\"a b\""), Term::Phrase("a b".to_string()));
+    }
+
+    #[test]
+    fn unknown_field_names_search_as_text() {
+        assert_eq!(parse_term_of("std::fs"), Term::Text("std::fs".to_string()));
+        assert_eq!(
+            parse_term_of("meeting:notes"),
+            Term::Text("meeting:notes".to_string())
+        );
+        assert_eq!(parse_term_of("c:"), Term::Text("c:".to_string()));
+    }
+
+    #[test]
+    fn parses_ext() {
+        assert_eq!(
+            parse_term_of("ext:JPG,.png"),
+            Term::Extension(vec!["jpg".to_string(), "png".to_string()])
+        );
+        assert_eq!(parse_error("ext:"), "Missing value after 'ext:'");
+    }
+
+    #[test]
+    fn parses_dir_and_name() {
+        assert_eq!(parse_term_of("dir:src"), Term::Dir("src".to_string()));
+        assert_eq!(
+            parse_term_of("name:\"my notes\""),
+            Term::Name("my notes".to_string())
+        );
+        // Field names are case-insensitive.
+        assert_eq!(parse_term_of("NAME:x"), Term::Name("x".to_string()));
+    }
+
+    #[test]
+    fn parses_size() {
+        assert_eq!(
+            parse_term_of("size:>10M"),
+            Term::Size(Comparison::Greater, 10 << 20)
+        );
+        assert_eq!(
+            parse_term_of("size:<=1.5k"),
+            Term::Size(Comparison::LessOrEqual, 1536)
+        );
+        assert_eq!(
+            parse_term_of("size:100"),
+            Term::Size(Comparison::Equal, 100)
+        );
+        assert!(parse("size:big").is_err());
+    }
+
+    #[test]
+    fn parses_modified_ages() {
+        assert!(matches!(
+            parse_term_of("modified:<7d"),
+            Term::Modified {
+                after: Some(_),
+                before: None
+            }
+        ));
+        assert!(matches!(
+            parse_term_of("mtime:>1y"),
+            Term::Modified {
+                after: None,
+                before: Some(_)
+            }
+        ));
+        assert!(parse("modified:soon").is_err());
+    }
+
+    #[test]
+    fn parses_modified_dates() {
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        let day_start = local_timestamp(date).unwrap();
+        let next_day_start = local_timestamp(date.succ_opt().unwrap()).unwrap();
+        assert_eq!(
+            parse_term_of("modified:2024-05-01"),
+            Term::Modified {
+                after: Some(day_start),
+                before: Some(next_day_start)
+            }
+        );
+        assert_eq!(
+            parse_term_of("modified:>=2024-05-01"),
+            Term::Modified {
+                after: Some(day_start),
+                before: None
+            }
+        );
+        assert_eq!(
+            parse_term_of("modified:<2024-05-01"),
+            Term::Modified {
+                after: None,
+                before: Some(day_start)
+            }
+        );
+    }
+
+    #[test]
+    fn parses_type() {
+        assert_eq!(parse_term_of("type:dir"), Term::Kind(EntryKind::Dir));
+        assert_eq!(parse_term_of("type:f"), Term::Kind(EntryKind::File));
+        assert_eq!(parse_term_of("kind:link"), Term::Kind(EntryKind::Symlink));
+        assert!(parse("type:socket").is_err());
+    }
+
+    #[test]
+    fn compiles_negation_and_fields_to_sql() {
+        let (sql, params) = parse("-ext:rs size:>1k")
+            .unwrap()
+            .to_sql(MatchMode::Substring);
+        assert_eq!(sql, "(NOT COALESCE(extension IN (?), 0) AND size > ?)");
+        assert_eq!(
+            params,
+            [Value::Text("rs".to_string()), Value::Integer(1024)]
+        );
+    }
+
+    #[test]
+    fn escapes_like_wildcards_in_words() {
+        assert_eq!(like_pattern("100%_a*b?"), "100\\%\\_a%b_");
+        assert_eq!(fuzzy_pattern("a%"), "%a%\\%%");
+    }
+
+    #[test]
+    fn fuzzy_patterns_skip_wildcards() {
+        assert_eq!(fuzzy_pattern("qfcfg"), "%q%f%c%f%g%");
+        assert_eq!(fuzzy_pattern("q*f?g"), "%q%f%g%");
+        assert_eq!(fuzzy_pattern("*"), "%");
+    }
+}
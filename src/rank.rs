@@ -0,0 +1,407 @@
+use crate::db::{FileEntry, MatchMode};
+use std::cmp::{Ordering, Reverse};
+use std::path::{Component, Path};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DAY: i64 = 24 * 60 * 60;
+
+// How well a single search word matches the basename or a directory component. The tiers are
+// further apart than the largest depth penalty plus recency bonus, so for a one-word query a
+// better kind of match always ranks first, and basename matches beat directory matches of any
+// kind. Depth and recency only order matches of the same kind.
+const BASENAME_EXACT: i64 = 600;
+const BASENAME_PREFIX: i64 = 500;
+const BASENAME_SUBSTRING: i64 = 400;
+const DIR_EXACT: i64 = 300;
+const DIR_PREFIX: i64 = 200;
+const DIR_SUBSTRING: i64 = 100;
+const _: () = assert!(
+    BASENAME_SUBSTRING - MAX_DEPTH_PENALTY > DIR_EXACT + MAX_RECENCY_BONUS
+        && DIR_SUBSTRING - MAX_DEPTH_PENALTY > MAX_RECENCY_BONUS
+);
+
+// Fuzzy matching, loosely following fzf: every matched character scores, matches right after a
+// separator or at a camelCase hump and runs of consecutive matches earn bonuses, gaps cost points.
+const FUZZY_MATCH: i64 = 16;
+const FUZZY_BOUNDARY_BONUS: i64 = 8;
+const FUZZY_CONSECUTIVE_BONUS: i64 = 4;
+const FUZZY_GAP_START: i64 = 3;
+const FUZZY_GAP_EXTENSION: i64 = 1;
+const FUZZY_BASENAME_BONUS: i64 = 30; // The whole word also matches within the basename
+
+const DEPTH_PENALTY: i64 = 3; // Per path component
+const MAX_DEPTH_PENALTY: i64 = 45;
+const MAX_RECENCY_BONUS: i64 = 20;
+
+// A search result with its relevance score. Orders best match first; ties keep shorter paths
+// first, then alphabetical order, so results are stable between identical queries.
+#[derive(Debug, Clone)]
+pub struct ScoredEntry {
+    pub score: i64,
+    pub entry: FileEntry,
+}
+
+impl Ord for ScoredEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        Reverse(self.score)
+            .cmp(&Reverse(other.score))
+            .then_with(|| self.entry.path.len().cmp(&other.entry.path.len()))
+            .then_with(|| self.entry.path.cmp(&other.entry.path))
+    }
+}
+
+impl PartialOrd for ScoredEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ScoredEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ScoredEntry {}
+
+// Scores entries against one query; built once per search so the words are only prepared once.
+pub struct Ranker {
+    words: Vec<String>,
+    mode: MatchMode,
+    now: i64,
+}
+
+impl Ranker {
+    pub fn new(terms: &[String], mode: MatchMode) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        Self {
+            words: search_words(terms, mode),
+            mode,
+            now,
+        }
+    }
+
+    pub fn rank(&self, entry: FileEntry) -> ScoredEntry {
+        ScoredEntry {
+            score: score(&entry, &self.words, self.mode, self.now),
+            entry,
+        }
+    }
+}
+
+// Wildcards can't be scored, so in substring mode each word is reduced to its literal pieces.
+fn search_words(terms: &[String], mode: MatchMode) -> Vec<String> {
+    let words = terms.iter();
+    match mode {
+        MatchMode::Substring => words
+            .flat_map(|word| word.split(['*', '?']))
+            .filter(|piece| !piece.is_empty())
+            .map(|piece| piece.to_lowercase())
+            .collect(),
+        MatchMode::Fuzzy => words.map(|word| word.to_lowercase()).collect(),
+        // A pattern has no words to score, so regex results are ordered by depth and recency alone.
+        MatchMode::Regex => Vec::new(),
+    }
+}
+
+// fzf's v1 algorithm: find the leftmost occurrence of the pattern as a subsequence, then walk
+// back from where it ends to find the shortest window that still contains it. Returns the
+// score and the byte offset of every matched character. Wildcards in the pattern are skipped, as
+// they are in `query`'s fuzzy condition.
+pub fn fuzzy_match(text: &str, pattern: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !matches!(c, '*' | '?'))
+        .map(fold_case)
+        .collect();
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    let mut matched = 0;
+    let mut end = None;
+    for (i, &(_, c)) in chars.iter().enumerate() {
+        if fold_case(c) == pattern[matched] {
+            matched += 1;
+            if matched == pattern.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if fold_case(chars[i].1) == pattern[matched - 1] {
+            matched -= 1;
+            if matched == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut positions = Vec::with_capacity(pattern.len());
+    for (i, &(_, c)) in chars.iter().enumerate().take(end + 1).skip(start) {
+        if positions.len() < pattern.len() && fold_case(c) == pattern[positions.len()] {
+            positions.push(i);
+        }
+    }
+
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    for &i in &positions {
+        score += FUZZY_MATCH;
+        if is_word_boundary(&chars, i) {
+            score += FUZZY_BOUNDARY_BONUS;
+        }
+        if let Some(p) = previous {
+            let gap = (i - p - 1) as i64;
+            if gap == 0 {
+                score += FUZZY_CONSECUTIVE_BONUS;
+            } else {
+                score -= FUZZY_GAP_START + FUZZY_GAP_EXTENSION * (gap - 1);
+            }
+        }
+        previous = Some(i);
+    }
+
+    Some((score, positions.iter().map(|&i| chars[i].0).collect()))
+}
+
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn is_word_boundary(chars: &[(usize, char)], i: usize) -> bool {
+    let Some(&(_, before)) = i.checked_sub(1).and_then(|p| chars.get(p)) else {
+        return true;
+    };
+    let current = chars[i].1;
+    matches!(before, '/' | '\\' | '_' | '-' | '.' | ' ')
+        || (before.is_lowercase() && current.is_uppercase())
+}
+
+fn score(entry: &FileEntry, words: &[String], mode: MatchMode, now: i64) -> i64 {
+    let path = Path::new(&entry.path);
+    let basename = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let stem = path
+        .file_stem()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    let dirs: Vec<String> = path
+        .parent()
+        .map(|parent| {
+            parent
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => name.to_str().map(str::to_lowercase),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut score: i64 = words
+        .iter()
+        .map(|word| match mode {
+            MatchMode::Substring | MatchMode::Regex => word_score(word, &basename, &stem, &dirs),
+            MatchMode::Fuzzy => fuzzy_word_score(word, &entry.path, &basename),
+        })
+        .sum();
+
+    let depth = path.components().count() as i64;
+    score -= (depth * DEPTH_PENALTY).min(MAX_DEPTH_PENALTY);
+    score += recency_bonus(entry.mtime, now);
+    score
+}
+
+fn word_score(word: &str, basename: &str, stem: &str, dirs: &[String]) -> i64 {
+    if basename == word || stem == word {
+        BASENAME_EXACT
+    } else if basename.starts_with(word) {
+        BASENAME_PREFIX
+    } else if basename.contains(word) {
+        BASENAME_SUBSTRING
+    } else if dirs.iter().any(|d| d == word) {
+        DIR_EXACT
+    } else if dirs.iter().any(|d| d.starts_with(word)) {
+        DIR_PREFIX
+    } else if dirs.iter().any(|d| d.contains(word)) {
+        DIR_SUBSTRING
+    } else {
+        // Matched across a separator, e.g. "src/main"
+        0
+    }
+}
+
+fn fuzzy_word_score(word: &str, path: &str, basename: &str) -> i64 {
+    let path_score = fuzzy_match(path, word).map_or(0, |(score, _)| score);
+    if fuzzy_match(basename, word).is_some() {
+        path_score + FUZZY_BASENAME_BONUS
+    } else {
+        path_score
+    }
+}
+
+fn recency_bonus(mtime: Option<i64>, now: i64) -> i64 {
+    let Some(mtime) = mtime else {
+        return 0;
+    };
+    match now - mtime {
+        age if age < DAY => MAX_RECENCY_BONUS,
+        age if age < 7 * DAY => 15,
+        age if age < 30 * DAY => 10,
+        age if age < 365 * DAY => 5,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn entry(path: &str, mtime: Option<i64>) -> FileEntry {
+        FileEntry {
+            path: path.to_string(),
+            size: None,
+            mtime,
+            kind: None,
+            extension: None,
+            link_target: None,
+        }
+    }
+
+    fn ranked(paths: &[(&str, Option<i64>)], query: &str) -> Vec<String> {
+        let words = search_words(&[query.to_string()], MatchMode::Substring);
+        let mut results: Vec<ScoredEntry> = paths
+            .iter()
+            .map(|&(path, mtime)| {
+                let entry = entry(path, mtime);
+                ScoredEntry {
+                    score: score(&entry, &words, MatchMode::Substring, NOW),
+                    entry,
+                }
+            })
+            .collect();
+        results.sort();
+        results.into_iter().map(|r| r.entry.path).collect()
+    }
+
+    #[test]
+    fn word_score_prefers_basename_then_directory_matches() {
+        let dirs = vec!["home".to_string(), "reports".to_string()];
+        assert_eq!(
+            word_score("notes.md", "notes.md", "notes", &dirs),
+            BASENAME_EXACT
+        );
+        assert_eq!(
+            word_score("notes", "notes.md", "notes", &dirs),
+            BASENAME_EXACT
+        );
+        assert_eq!(
+            word_score("not", "notes.md", "notes", &dirs),
+            BASENAME_PREFIX
+        );
+        assert_eq!(
+            word_score("tes", "notes.md", "notes", &dirs),
+            BASENAME_SUBSTRING
+        );
+        assert_eq!(word_score("reports", "notes.md", "notes", &dirs), DIR_EXACT);
+        assert_eq!(word_score("rep", "notes.md", "notes", &dirs), DIR_PREFIX);
+        assert_eq!(
+            word_score("port", "notes.md", "notes", &dirs),
+            DIR_SUBSTRING
+        );
+        assert_eq!(word_score("s/notes", "notes.md", "notes", &dirs), 0);
+    }
+
+    #[test]
+    fn basename_matches_beat_directory_matches_despite_depth_and_age() {
+        let results = ranked(
+            &[
+                ("/tmp/qr/d/report/x.txt", Some(NOW)),
+                (
+                    "/tmp/qr/d/a/b/c/d/e/f/g/h/old-report-x.txt",
+                    Some(NOW - 2 * 365 * DAY),
+                ),
+            ],
+            "report",
+        );
+        assert_eq!(
+            results,
+            [
+                "/tmp/qr/d/a/b/c/d/e/f/g/h/old-report-x.txt",
+                "/tmp/qr/d/report/x.txt"
+            ]
+        );
+    }
+
+    #[test]
+    fn shallower_and_newer_matches_of_the_same_kind_rank_first() {
+        let by_depth = ranked(
+            &[
+                ("/a/b/c/d/report.txt", Some(NOW)),
+                ("/a/report.txt", Some(NOW)),
+            ],
+            "report",
+        );
+        assert_eq!(by_depth, ["/a/report.txt", "/a/b/c/d/report.txt"]);
+
+        let by_age = ranked(
+            &[
+                ("/a/report.txt", Some(NOW - 2 * 365 * DAY)),
+                ("/b/report.txt", Some(NOW - DAY / 2)),
+            ],
+            "report",
+        );
+        assert_eq!(by_age, ["/b/report.txt", "/a/report.txt"]);
+    }
+
+    #[test]
+    fn ties_keep_shorter_paths_first_then_alphabetical() {
+        let results = ranked(&[("/x/bb.txt", None), ("/x/ab.txt", None)], "b");
+        assert_eq!(results, ["/x/bb.txt", "/x/ab.txt"]);
+    }
+
+    #[test]
+    fn fuzzy_match_finds_abbreviations() {
+        let (score, positions) = fuzzy_match("quickfind/src/config.rs", "qfcfg").unwrap();
+        assert_eq!(positions, [0, 5, 12, 17, 19]);
+        assert!(score > 0);
+    }
+
+    #[test]
+    fn fuzzy_match_folds_case_and_returns_byte_offsets_for_non_ascii_text() {
+        let (_, positions) = fuzzy_match("/Café/Über.txt", "éü").unwrap();
+        assert_eq!(positions, [4, 7]);
+        assert_eq!(&"/Café/Über.txt"[7..9], "Ü");
+    }
+
+    #[test]
+    fn fuzzy_match_needs_every_character_in_order() {
+        assert!(fuzzy_match("src/main.rs", "xyz").is_none());
+        assert!(fuzzy_match("src/main.rs", "nima").is_none());
+        assert_eq!(fuzzy_match("src/main.rs", ""), Some((0, Vec::new())));
+    }
+
+    #[test]
+    fn fuzzy_match_skips_wildcards() {
+        assert_eq!(
+            fuzzy_match("src/config.rs", "c*f?g"),
+            fuzzy_match("src/config.rs", "cfg")
+        );
+    }
+}
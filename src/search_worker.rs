@@ -0,0 +1,138 @@
+use crate::db::{self, SearchOptions};
+use crate::rank::ScoredEntry;
+use eyre::Result;
+use rusqlite::{Connection, InterruptHandle};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const DEBOUNCE: Duration = Duration::from_millis(60); // Requests closer together than this are searched once
+
+struct SearchRequest {
+    generation: u64,
+    term: String,
+    options: SearchOptions,
+}
+
+// Every update names the request it answers, so the caller can tell which results are current.
+pub enum SearchUpdate {
+    Results {
+        generation: u64,
+        batch: Vec<ScoredEntry>,
+    },
+    Finished {
+        generation: u64,
+    },
+    Failed {
+        generation: u64,
+        message: String,
+    },
+}
+
+// Runs searches on a thread with its own connection, so typing never waits on SQLite.
+// Each request supersedes the ones before it: a running query is interrupted and nothing more
+// is sent for it.
+pub struct SearchWorker {
+    requests: Sender<SearchRequest>,
+    updates: Receiver<SearchUpdate>,
+    latest: Arc<AtomicU64>,
+    interrupt: InterruptHandle,
+}
+
+impl SearchWorker {
+    pub fn spawn() -> Result<Self> {
+        let conn = db::get_connection()?;
+        let interrupt = conn.get_interrupt_handle();
+        let latest = Arc::new(AtomicU64::new(0));
+        let (requests, request_rx) = mpsc::channel();
+        let (update_tx, updates) = mpsc::channel();
+
+        let worker_latest = Arc::clone(&latest);
+        thread::spawn(move || run_worker(conn, request_rx, update_tx, worker_latest));
+
+        Ok(Self {
+            requests,
+            updates,
+            latest,
+            interrupt,
+        })
+    }
+
+    // Returns the generation that updates for this search will carry.
+    pub fn search(&self, term: &str, options: &SearchOptions) -> u64 {
+        let generation = self.cancel();
+        // A worker that has gone away just never answers, which leaves the old results on screen.
+        let _ = self.requests.send(SearchRequest {
+            generation,
+            term: term.to_string(),
+            options: options.clone(),
+        });
+        generation
+    }
+
+    // Stops the running search and drops queued ones; updates for them are never sent.
+    // The interrupt comes before any newer request is sent, and SQLite clears it when the next
+    // statement starts on an idle connection, so it can only ever hit a superseded query.
+    pub fn cancel(&self) -> u64 {
+        let generation = self.latest.fetch_add(1, Ordering::SeqCst) + 1;
+        self.interrupt.interrupt();
+        generation
+    }
+
+    pub fn try_recv(&self) -> Option<SearchUpdate> {
+        self.updates.try_recv().ok()
+    }
+}
+
+impl Drop for SearchWorker {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+fn run_worker(
+    conn: Connection,
+    requests: Receiver<SearchRequest>,
+    updates: Sender<SearchUpdate>,
+    latest: Arc<AtomicU64>,
+) {
+    while let Ok(mut request) = requests.recv() {
+        // Wait for typing to pause, keeping only the newest request.
+        loop {
+            match requests.recv_timeout(DEBOUNCE) {
+                Ok(newer) => request = newer,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+
+        let generation = request.generation;
+        let is_current = || latest.load(Ordering::SeqCst) == generation;
+        if !is_current() {
+            continue;
+        }
+
+        let result = db::search_files_in_batches(&conn, &request.term, &request.options, |batch| {
+            is_current()
+                && updates
+                    .send(SearchUpdate::Results { generation, batch })
+                    .is_ok()
+        });
+        // Superseded searches end quietly, whether they finished or were interrupted.
+        if !is_current() {
+            continue;
+        }
+        let update = match result {
+            Ok(()) => SearchUpdate::Finished { generation },
+            Err(e) => SearchUpdate::Failed {
+                generation,
+                message: e.to_string(),
+            },
+        };
+        if updates.send(update).is_err() {
+            return;
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+// Key bindings and the `qcd` function, printed by `quickfind init` for the shell to evaluate.
+pub fn init_script(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => include_str!("shell/quickfind.bash"),
+        Shell::Zsh => include_str!("shell/quickfind.zsh"),
+        Shell::Fish => include_str!("shell/quickfind.fish"),
+    }
+}
@@ -0,0 +1,20 @@
+# quickfind shell integration for bash: eval "$(quickfind init bash)"
+# Ctrl-T inserts the chosen paths at the cursor, qcd changes to the chosen directory.
+
+__quickfind_select() {
+  local selected
+  selected=$(quickfind --print-selection | while IFS= read -r line; do printf '%q ' "$line"; done)
+  READLINE_LINE="${READLINE_LINE:0:$READLINE_POINT}$selected${READLINE_LINE:$READLINE_POINT}"
+  READLINE_POINT=$((READLINE_POINT + ${#selected}))
+}
+
+bind -m emacs-standard -x '"\C-t": __quickfind_select'
+bind -m vi-insert -x '"\C-t": __quickfind_select'
+
+qcd() {
+  local dir
+  dir=$(quickfind --print-selection "type:dir $*" | head -n 1)
+  [ -n "$dir" ] || return 1
+  [ -d "$dir" ] || dir=$(dirname -- "$dir")
+  cd -- "$dir" || return
+}
@@ -0,0 +1,20 @@
+# quickfind shell integration for fish: quickfind init fish | source
+# Ctrl-T inserts the chosen paths at the cursor, qcd changes to the chosen directory.
+
+function __quickfind_select
+    set -l selected (quickfind --print-selection | string escape)
+    if test (count $selected) -gt 0
+        commandline --insert -- (string join ' ' $selected)' '
+    end
+    commandline --function repaint
+end
+
+bind \ct __quickfind_select
+bind -M insert \ct __quickfind_select 2>/dev/null
+
+function qcd
+    set -l dir (quickfind --print-selection "type:dir $argv" | head -n 1)
+    test -n "$dir"; or return 1
+    test -d "$dir"; or set dir (dirname -- $dir)
+    cd -- $dir
+end
@@ -0,0 +1,220 @@
+use crate::config::Config;
+use crate::db::{self, EntryKind};
+use crate::indexing;
+use crate::output::{format_age, format_mtime, format_size};
+use eyre::Result;
+use rusqlite::Connection;
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const TOP_EXTENSIONS: usize = 10;
+const LISTED_ERRORS: usize = 10; // Unless every one was asked for
+
+// With `quick`, skips checking which entries' paths still exist, which touches every one on disk.
+pub fn print_stats(
+    conn: &Connection,
+    config: &Config,
+    quick: bool,
+    all_errors: bool,
+) -> Result<ExitCode> {
+    let db_path = db::get_db_path()?;
+    let db_size = fs::metadata(&db_path)?.len();
+    println!(
+        "Database: {} ({})",
+        db_path.display(),
+        format_size(Some(db_size))
+    );
+
+    let counts = db::count_by_kind(conn)?;
+    let total: usize = counts.iter().map(|(_, count)| count).sum();
+    let breakdown: Vec<String> = counts
+        .iter()
+        .map(|(kind, count)| {
+            let label = match kind {
+                EntryKind::File => "files",
+                EntryKind::Dir => "directories",
+                EntryKind::Symlink => "symlinks",
+            };
+            format!("{} {}", count, label)
+        })
+        .collect();
+    if breakdown.is_empty() {
+        println!("Entries:  0");
+    } else {
+        println!("Entries:  {} ({})", total, breakdown.join(", "));
+    }
+    if !quick {
+        let missing = indexing::prune_missing(conn, true, false)?;
+        println!(
+            "Missing:  {} entries point to paths that no longer exist{}",
+            missing,
+            if missing > 0 {
+                " (`quickfind prune` removes them)"
+            } else {
+                ""
+            }
+        );
+    }
+
+    print_roots(conn, config, total)?;
+    print_walk_errors(conn, all_errors)?;
+
+    for (by_size, title) in [(false, "count"), (true, "size")] {
+        let extensions = db::top_extensions(conn, by_size, TOP_EXTENSIONS)?;
+        if extensions.is_empty() {
+            continue;
+        }
+        println!();
+        println!("Top extensions by {}:", title);
+        for stats in extensions {
+            println!(
+                "  {:<12} {:>10} {:>9}",
+                stats.extension,
+                stats.count,
+                format_size(Some(stats.size))
+            );
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+// Configured roots first, then any that were indexed explicitly or have since been removed from
+// the configuration.
+fn print_roots(conn: &Connection, config: &Config, total: usize) -> Result<()> {
+    let runs = db::last_index_runs(conn)?;
+    let mut roots: Vec<&str> = config.include.iter().map(String::as_str).collect();
+    for run in &runs {
+        if !roots.contains(&run.root.as_str()) {
+            roots.push(&run.root);
+        }
+    }
+    // Nested roots share entries, so only top-level ones add up to the total.
+    let is_nested = |root: &str| {
+        roots
+            .iter()
+            .any(|other| *other != root && Path::new(root).starts_with(other))
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let mut covered = 0;
+    println!();
+    println!("Roots:");
+    for root in &roots {
+        let count = db::count_under(conn, root)?;
+        if !is_nested(root) {
+            covered += count;
+        }
+        let indexed = match runs.iter().find(|run| run.root == *root) {
+            Some(run) => format!(
+                "indexed {} ({} ago) in {:.1?}",
+                format_mtime(Some(run.started_at)),
+                format_age(now - run.started_at),
+                Duration::from_millis(run.duration_ms.max(0) as u64)
+            ),
+            None => "never indexed".to_string(),
+        };
+        println!("  {}: {} entries, {}", root, count, indexed);
+    }
+    if total > covered {
+        println!("  (elsewhere): {} entries", total - covered);
+    }
+    Ok(())
+}
+
+fn print_walk_errors(conn: &Connection, all_errors: bool) -> Result<()> {
+    let errors = db::walk_errors(conn)?;
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    let mut kinds: Vec<(&str, usize)> = Vec::new();
+    for (_, error) in &errors {
+        match kinds.iter_mut().find(|(kind, _)| *kind == error.kind) {
+            Some((_, count)) => *count += 1,
+            None => kinds.push((&error.kind, 1)),
+        }
+    }
+    kinds.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let kinds: Vec<String> = kinds
+        .iter()
+        .map(|(kind, count)| format!("{}: {}", kind, count))
+        .collect();
+
+    println!();
+    println!(
+        "Not indexed: {} paths in the latest runs ({})",
+        errors.len(),
+        kinds.join(", ")
+    );
+    let listed = if all_errors {
+        errors.len()
+    } else {
+        LISTED_ERRORS
+    };
+    for (root, error) in errors.iter().take(listed) {
+        let path = if error.path.is_empty() {
+            root
+        } else {
+            &error.path
+        };
+        println!("  {}: {}", path, error.kind);
+    }
+    if errors.len() > listed {
+        println!(
+            "  … and {} more (`quickfind stats --errors` lists them all)",
+            errors.len() - listed
+        );
+    }
+    Ok(())
+}
+
+pub fn print_history(conn: &Connection, root: Option<&str>, limit: usize) -> Result<ExitCode> {
+    let runs = db::index_history(conn, root, limit)?;
+    if runs.is_empty() {
+        println!("No index runs recorded yet.");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let count = |value: Option<usize>| value.map_or_else(|| "-".to_string(), |v| v.to_string());
+    println!(
+        "{:<16}  {:>8}  {:>8}  {:>7}  {:>7}  {:>7}  {:>7}  {:>7}  {:>6}  {:<8}  Root",
+        "Started",
+        "Took",
+        "Files",
+        "Dirs",
+        "Ignored",
+        "Added",
+        "Updated",
+        "Removed",
+        "Errors",
+        "Config"
+    );
+    for run in runs {
+        println!(
+            "{:<16}  {:>8}  {:>8}  {:>7}  {:>7}  {:>7}  {:>7}  {:>7}  {:>6}  {:<8}  {}",
+            format_mtime(Some(run.started_at)),
+            format!(
+                "{:.1?}",
+                Duration::from_millis(run.duration_ms.max(0) as u64)
+            ),
+            count(run.files_found),
+            count(run.dirs_traversed),
+            count(run.items_ignored),
+            count(run.entries_added),
+            count(run.entries_updated),
+            count(run.entries_removed),
+            count(run.errors),
+            // The first few digits are plenty to spot a change.
+            run.config_hash
+                .as_deref()
+                .map_or("-", |hash| &hash[..8.min(hash.len())]),
+            run.root
+        );
+    }
+    Ok(ExitCode::SUCCESS)
+}
@@ -1,43 +1,82 @@
-use crate::config::load_config;
-use crate::db;
+use crate::clipboard;
+use crate::config::{load_config, Config};
+use crate::db::{self, EntryKind, FileEntry, MatchMode, SearchOptions};
+use crate::output::{format_age, format_mtime, format_size};
+use crate::preview::{Preview, PreviewLoader};
+use crate::query;
+use crate::rank::{self, ScoredEntry};
+use crate::search_worker::{SearchUpdate, SearchWorker};
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use eyre::Result;
+use regex::Regex;
 use rusqlite::Connection;
-use std::io::{self};
+use std::io::{self, BufWriter, IsTerminal, Write};
 use std::{
-    path::PathBuf,
+    collections::BTreeSet,
+    path::{self, Path, PathBuf},
     process::Command,
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Style},
     text::{Span, Spans, Text},
     widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
     Frame, Terminal,
 };
 
+const SEARCH_POLL_INTERVAL: Duration = Duration::from_millis(16);
+const STALE_INDEX_AGE: i64 = 24 * 60 * 60; // Seconds before the summary says how old the index is
+
 enum Focus {
     Search,
     Results,
 }
 
-pub fn run_tui(conn: &Connection, initial_search: Option<String>) -> Result<()> {
+// What of each path the copy keys put on the clipboard.
+enum CopyTarget {
+    Absolute,
+    Relative, // To the directory quickfind was started in
+    Basename,
+    Parent,
+}
+
+// Returns the paths chosen to be printed on exit. With `print_selection`, Enter chooses instead of
+// opening, for shell integration.
+pub fn run_tui(
+    conn: &Connection,
+    initial_search: Option<String>,
+    regex: bool,
+    print_selection: bool,
+) -> Result<Vec<String>> {
     // setup terminal
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
+    // Drawn on stderr when stdout is captured, as in `vim $(quickfind)`, so that only the
+    // printed paths end up in the capture.
+    let mut output: Box<dyn Write> = if io::stdout().is_terminal() {
+        Box::new(io::stdout())
+    } else {
+        Box::new(BufWriter::new(io::stderr()))
+    };
+    execute!(output, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(output);
     let mut terminal = Terminal::new(backend)?;
 
     // create app and run it
     let tick_rate = Duration::from_millis(250);
-    let res = run_app(&mut terminal, conn, initial_search, tick_rate);
+    let res = run_app(
+        &mut terminal,
+        conn,
+        initial_search,
+        regex,
+        print_selection,
+        tick_rate,
+    );
 
     // restore terminal
     disable_raw_mode()?;
@@ -48,11 +87,13 @@ pub fn run_tui(conn: &Connection, initial_search: Option<String>) -> Result<()>
     )?;
     terminal.show_cursor()?;
 
-    if let Err(err) = res {
-        println!("{:?}", err)
+    match res {
+        Ok(paths) => Ok(paths),
+        Err(err) => {
+            eprintln!("{:?}", err);
+            Ok(vec![])
+        }
     }
-
-    Ok(())
 }
 
 fn parse_color(s: &str) -> Option<Color> {
@@ -89,12 +130,150 @@ fn handle_file_opening(path: &str, error_message: &mut Option<String>) {
     }
 }
 
-fn run_app<B: Backend>(
+// Results of the newest search, merged in ranked order as the worker streams them in.
+// The previous results stay on screen until the first answer to a new search arrives.
+struct LiveSearch {
+    worker: SearchWorker,
+    requested: u64, // Generation of the newest search
+    shown: u64,     // Generation `results` belong to
+    searching: bool,
+    results: Vec<ScoredEntry>,
+}
+
+impl LiveSearch {
+    fn start(&mut self, search_input: &str, search_options: &SearchOptions) {
+        self.requested = self.worker.search(search_input, search_options);
+        self.searching = true;
+    }
+
+    // Runs the search on the UI thread instead, for actions that need its results straight away.
+    fn finish_now(
+        &mut self,
+        conn: &Connection,
+        search_input: &str,
+        search_options: &SearchOptions,
+        error_message: &mut Option<String>,
+    ) {
+        self.requested = self.worker.cancel();
+        self.shown = self.requested;
+        self.searching = false;
+        self.results = match db::search_files(conn, search_input, search_options) {
+            Ok(results) => {
+                *error_message = None;
+                results
+            }
+            Err(e) => {
+                *error_message = Some(format!("Invalid query: {}", e));
+                vec![]
+            }
+        };
+    }
+
+    // A query that doesn't parse shows why in the error line instead of results.
+    // Drains everything the worker has sent so far but merges it into the list only once, so a
+    // search streaming large batches can't keep the loop from drawing and handling keys.
+    fn receive(&mut self, results_state: &mut ListState, error_message: &mut Option<String>) {
+        let mut incoming = Vec::new();
+        while let Some(update) = self.worker.try_recv() {
+            let generation = match &update {
+                SearchUpdate::Results { generation, .. }
+                | SearchUpdate::Finished { generation }
+                | SearchUpdate::Failed { generation, .. } => *generation,
+            };
+            if generation != self.requested {
+                continue;
+            }
+            if generation != self.shown {
+                self.shown = generation;
+                self.results.clear();
+                incoming.clear();
+                results_state.select(Some(0));
+                *error_message = None;
+            }
+
+            match update {
+                SearchUpdate::Results { batch, .. } => incoming.extend(batch),
+                SearchUpdate::Finished { .. } => self.searching = false,
+                SearchUpdate::Failed { message, .. } => {
+                    self.searching = false;
+                    *error_message = Some(format!("Invalid query: {}", message));
+                }
+            }
+        }
+
+        if !incoming.is_empty() {
+            incoming.sort();
+            self.results = merge_sorted(std::mem::take(&mut self.results), incoming);
+        }
+    }
+}
+
+fn merge_sorted(left: Vec<ScoredEntry>, right: Vec<ScoredEntry>) -> Vec<ScoredEntry> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if r < l {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    merged
+}
+
+// Preview of the selected result, loaded in the background whenever the selection changes.
+// The previous preview stays up until the next one is ready, which keeps scrolling from flickering.
+struct PreviewPane {
+    loader: PreviewLoader,
+    enabled: bool,
+    requested: Option<String>,
+    current: Option<(String, Preview)>,
+}
+
+impl PreviewPane {
+    fn update(&mut self, selected_path: Option<&str>) {
+        if !self.enabled {
+            return;
+        }
+        let Some(path) = selected_path else {
+            self.requested = None;
+            self.current = None;
+            return;
+        };
+        if self.requested.as_deref() != Some(path) {
+            self.loader.request(path);
+            self.requested = Some(path.to_string());
+        }
+        if let Some(preview) = self.loader.try_recv() {
+            self.current = Some(preview);
+        }
+    }
+
+    fn loading(&self) -> bool {
+        self.enabled
+            && self.requested.is_some()
+            && self.requested.as_deref() != self.current.as_ref().map(|(path, _)| path.as_str())
+    }
+
+    fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        // Reloaded when shown again, since the file may have changed in the meantime.
+        self.requested = None;
+        self.current = None;
+    }
+}
+
+fn run_app<B: Backend + Write>(
     terminal: &mut Terminal<B>,
     conn: &Connection,
     initial_search: Option<String>,
+    regex: bool,
+    print_selection: bool,
     tick_rate: Duration,
-) -> io::Result<()> {
+) -> io::Result<Vec<String>> {
     let config = load_config().unwrap_or_default();
     let highlight_color = config
         .highlight_color
@@ -102,60 +281,142 @@ fn run_app<B: Backend>(
         .and_then(|s| parse_color(s))
         .unwrap_or(Color::DarkGray);
     let preferred_editor = config.editor.clone();
+    let clipboard_command = config.clipboard_command.clone();
+    let index_notice = index_age_notice(conn, &config);
+    // Ctrl-R switches between regex and the configured mode (substring if that's regex too).
+    let default_mode = match config.match_mode {
+        MatchMode::Regex => MatchMode::Substring,
+        mode => mode,
+    };
+    let mut search_options = SearchOptions {
+        include_dirs: config.search_dirs,
+        mode: if regex {
+            MatchMode::Regex
+        } else {
+            config.match_mode
+        },
+    };
 
     let mut last_tick = Instant::now();
     let mut search_input = initial_search.clone().unwrap_or_default();
     let mut cursor_position = 0;
     let mut error_message: Option<String> = None;
+    let mut status_message: Option<String> = None; // Confirms an action until the next key
 
-    let mut search_results = if let Some(term) = initial_search {
-        db::search_files(conn, &term).unwrap_or_default()
-    } else {
-        vec![]
+    let mut search = LiveSearch {
+        worker: SearchWorker::spawn().map_err(|e| io::Error::other(e.to_string()))?,
+        requested: 0,
+        shown: 0,
+        searching: false,
+        results: Vec::new(),
+    };
+    if let Some(term) = initial_search {
+        search.start(&term, &search_options);
+    }
+
+    let mut preview = PreviewPane {
+        loader: PreviewLoader::spawn(),
+        enabled: config.preview,
+        requested: None,
+        current: None,
     };
 
     let mut results_state = ListState::default();
     results_state.select(Some(0));
+    let mut results_offset = 0;
     let mut focus = Focus::Search;
+    // Paths rather than positions, so marks survive the results changing under them.
+    let mut marked: BTreeSet<String> = BTreeSet::new();
 
     loop {
+        search.receive(&mut results_state, &mut error_message);
+        let selected_path = results_state
+            .selected()
+            .and_then(|i| search.results.get(i))
+            .map(|result| result.entry.path.as_str());
+        preview.update(selected_path);
+
         terminal.draw(|f| {
             ui(
                 f,
                 &search_input,
                 &mut cursor_position,
-                &search_results,
+                &search.results,
                 &mut results_state,
+                &mut results_offset,
                 &focus,
                 &highlight_color,
                 &error_message, // Pass the error_message
+                &status_message,
+                &index_notice,
+                &search_options,
+                search.searching,
+                &preview,
+                &marked,
             )
         })?;
 
-        let timeout = tick_rate
+        let mut timeout = tick_rate
             .checked_sub(last_tick.elapsed())
             .unwrap_or_else(|| Duration::from_secs(0));
+        if search.searching || preview.loading() {
+            // Wake up often enough to show results as they stream in.
+            timeout = timeout.min(SEARCH_POLL_INTERVAL);
+        }
 
         if crossterm::event::poll(timeout)? {
             if let Event::Key(key) = event::read()? {
+                status_message = None;
+                // Ctrl-D toggles directories in the results regardless of focus
+                if key.code == KeyCode::Char('d') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    search_options.include_dirs = !search_options.include_dirs;
+                    search.start(&search_input, &search_options);
+                    continue;
+                }
+                // Ctrl-P shows or hides the preview pane regardless of focus
+                if key.code == KeyCode::Char('p') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    preview.toggle();
+                    continue;
+                }
+                // Ctrl-R toggles regex mode regardless of focus
+                if key.code == KeyCode::Char('r') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    search_options.mode = if search_options.mode == MatchMode::Regex {
+                        default_mode
+                    } else {
+                        MatchMode::Regex
+                    };
+                    search.start(&search_input, &search_options);
+                    continue;
+                }
+
                 match focus {
                     Focus::Search => match key.code {
-                        KeyCode::Enter => {
-                            if !search_input.is_empty() {
-                                search_results =
-                                    db::search_files(conn, &search_input).unwrap_or_default();
-                                results_state.select(Some(0));
-                                focus = Focus::Results;
-                                if let Some(path) = search_results.get(0) {
-                                    handle_file_opening(path, &mut error_message);
+                        KeyCode::Enter if !search_input.is_empty() => {
+                            search.finish_now(
+                                conn,
+                                &search_input,
+                                &search_options,
+                                &mut error_message,
+                            );
+                            results_state.select(Some(0));
+                            focus = Focus::Results;
+                            if print_selection {
+                                let paths =
+                                    action_targets(&marked, &search.results, &results_state);
+                                if !paths.is_empty() {
+                                    return Ok(paths);
                                 }
+                            } else if let Some(ScoredEntry {
+                                entry: FileEntry { path, .. },
+                                ..
+                            }) = search.results.first()
+                            {
+                                handle_file_opening(path, &mut error_message);
                             }
                         }
-                        KeyCode::Down => {
-                            if !search_results.is_empty() {
-                                focus = Focus::Results;
-                                results_state.select(Some(0));
-                            }
+                        KeyCode::Down if !search.results.is_empty() => {
+                            focus = Focus::Results;
+                            results_state.select(Some(0));
                         }
                         KeyCode::Backspace => {
                             if cursor_position > 0 {
@@ -164,20 +425,13 @@ fn run_app<B: Backend>(
                             } else if !search_input.is_empty() {
                                 search_input.pop();
                             }
-                            search_results =
-                                db::search_files(conn, &search_input).unwrap_or_default();
-                            results_state.select(Some(0));
-                            error_message = None; // Clear error message on input change
+                            search.start(&search_input, &search_options);
                         }
                         KeyCode::Left => {
-                            if cursor_position > 0 {
-                                cursor_position -= 1;
-                            }
+                            cursor_position = cursor_position.saturating_sub(1);
                         }
-                        KeyCode::Right => {
-                            if cursor_position < search_input.len() {
-                                cursor_position += 1;
-                            }
+                        KeyCode::Right if cursor_position < search_input.len() => {
+                            cursor_position += 1;
                         }
                         KeyCode::Home => {
                             cursor_position = 0;
@@ -185,13 +439,11 @@ fn run_app<B: Backend>(
                         KeyCode::End => {
                             cursor_position = search_input.len();
                         }
-                        KeyCode::Delete => {
-                            if cursor_position < search_input.len() {
-                                search_input.remove(cursor_position);
-                            }
+                        KeyCode::Delete if cursor_position < search_input.len() => {
+                            search_input.remove(cursor_position);
                         }
                         KeyCode::Esc => {
-                            return Ok(());
+                            return Ok(vec![]);
                         }
                         KeyCode::Tab => {
                             focus = Focus::Results;
@@ -199,100 +451,141 @@ fn run_app<B: Backend>(
                         KeyCode::Char(c) => {
                             search_input.insert(cursor_position, c);
                             cursor_position += 1;
-                            search_results =
-                                db::search_files(conn, &search_input).unwrap_or_default();
-                            results_state.select(Some(0));
-                            error_message = None; // Clear error message on input change
+                            search.start(&search_input, &search_options);
                         }
                         _ => {}
                     },
                     Focus::Results => match key.code {
-                        KeyCode::Enter => {
-                            if let Some(selected) = results_state.selected() {
-                                if let Some(path) = search_results.get(selected) {
-                                    // Attempt to open the file
-                                    match opener::open(path) {
-                                        Ok(_) => {}
-                                        Err(e) => {
-                                            // Handle file not found or other errors
-                                            error_message =
-                                                Some(format!("Error opening file: {}", path));
-                                            eprintln!(
-                                                "Failed to open file: {}. Error: {:?}",
-                                                path, e
-                                            );
-                                            // If the error is indeed a file not found, we'd ideally want to re-index.
-                                            // For now, we'll just log the error.
-                                        }
-                                    }
-                                }
+                        KeyCode::Enter if print_selection => {
+                            let paths = action_targets(&marked, &search.results, &results_state);
+                            if !paths.is_empty() {
+                                return Ok(paths);
                             }
                         }
-                        KeyCode::Char('o') => {
-                            if let Some(selected) = results_state.selected() {
-                                if let Some(path) = search_results.get(selected) {
-                                    handle_file_opening(path, &mut error_message);
-                                }
+                        KeyCode::Enter | KeyCode::Char('o') => {
+                            for path in action_targets(&marked, &search.results, &results_state) {
+                                handle_file_opening(&path, &mut error_message);
                             }
                         }
                         KeyCode::Char('e') => {
+                            let paths = action_targets(&marked, &search.results, &results_state);
+                            if !paths.is_empty() {
+                                disable_raw_mode()?;
+                                execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+                                let editor_result =
+                                    open_file_with_editor(&paths, preferred_editor.clone());
+                                enable_raw_mode()?;
+                                execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+                                terminal.clear()?;
+                                if let Err(e) = editor_result {
+                                    error_message = Some(format!("Error opening file: {}", e));
+                                    eprintln!("Failed to open files: {:?}. Error: {:?}", paths, e);
+                                }
+                            }
+                        }
+                        KeyCode::Char(' ') => {
                             if let Some(selected) = results_state.selected() {
-                                if let Some(path) = search_results.get(selected) {
-                                    disable_raw_mode()?;
-                                    execute!(io::stdout(), LeaveAlternateScreen)?;
-                                    let editor_result =
-                                        open_file_with_editor(path, preferred_editor.clone());
-                                    enable_raw_mode()?;
-                                    execute!(io::stdout(), EnterAlternateScreen)?;
-                                    terminal.clear()?;
-                                    if let Err(e) = editor_result {
-                                        error_message = Some(format!("Error opening file: {}", e));
-                                        eprintln!("Failed to open file: {}. Error: {:?}", path, e);
+                                if let Some(ScoredEntry {
+                                    entry: FileEntry { path, .. },
+                                    ..
+                                }) = search.results.get(selected)
+                                {
+                                    if !marked.remove(path) {
+                                        marked.insert(path.clone());
+                                    }
+                                    // Move on, so a run of entries can be marked by holding Space.
+                                    if selected + 1 < search.results.len() {
+                                        results_state.select(Some(selected + 1));
                                     }
                                 }
                             }
                         }
-                        KeyCode::Down => {
-                            if !search_results.is_empty() {
-                                let i = match results_state.selected() {
-                                    Some(i) => (i + 1) % search_results.len(),
-                                    None => 0,
-                                };
-                                results_state.select(Some(i));
+                        KeyCode::Char('*') => {
+                            // Marks every result, or clears them all when they're already marked.
+                            if search
+                                .results
+                                .iter()
+                                .all(|r| marked.contains(&r.entry.path))
+                            {
+                                for result in &search.results {
+                                    marked.remove(&result.entry.path);
+                                }
+                            } else {
+                                marked.extend(search.results.iter().map(|r| r.entry.path.clone()));
                             }
                         }
-                        KeyCode::Up => {
-                            if !search_results.is_empty() {
-                                let i = match results_state.selected() {
-                                    Some(0) => {
-                                        focus = Focus::Search;
-                                        0
-                                    }
-                                    Some(i) => {
-                                        (i + search_results.len() - 1) % search_results.len()
+                        KeyCode::Char('P') => {
+                            return Ok(action_targets(&marked, &search.results, &results_state));
+                        }
+                        KeyCode::Char(c @ ('y' | 'Y' | 'b' | 'p')) => {
+                            let target = match c {
+                                'y' => CopyTarget::Absolute,
+                                'Y' => CopyTarget::Relative,
+                                'b' => CopyTarget::Basename,
+                                _ => CopyTarget::Parent,
+                            };
+                            let paths = action_targets(&marked, &search.results, &results_state);
+                            if !paths.is_empty() {
+                                let text = paths
+                                    .iter()
+                                    .map(|path| copy_text(path, &target))
+                                    .collect::<Vec<_>>()
+                                    .join("\n");
+                                match clipboard::copy(
+                                    &text,
+                                    clipboard_command.as_deref(),
+                                    terminal.backend_mut(),
+                                ) {
+                                    Ok(()) => {
+                                        error_message = None;
+                                        status_message = Some(if paths.len() == 1 {
+                                            format!("Copied {}", text)
+                                        } else {
+                                            format!("Copied {} paths", paths.len())
+                                        });
                                     }
-                                    None => 0,
-                                };
-                                results_state.select(Some(i));
+                                    Err(e) => error_message = Some(format!("{:#}", e)),
+                                }
                             }
                         }
+                        KeyCode::Down if !search.results.is_empty() => {
+                            let i = match results_state.selected() {
+                                Some(i) => (i + 1) % search.results.len(),
+                                None => 0,
+                            };
+                            results_state.select(Some(i));
+                        }
+                        KeyCode::Up if !search.results.is_empty() => {
+                            let i = match results_state.selected() {
+                                Some(0) => {
+                                    focus = Focus::Search;
+                                    0
+                                }
+                                Some(i) => (i + search.results.len() - 1) % search.results.len(),
+                                None => 0,
+                            };
+                            results_state.select(Some(i));
+                        }
                         KeyCode::Tab => {
                             focus = Focus::Search;
                         }
                         KeyCode::Esc => {
-                            return Ok(());
+                            return Ok(vec![]);
                         }
                         KeyCode::Char('d') => {
-                            if let Some(selected) = results_state.selected() {
-                                if let Some(path) = search_results.get(selected) {
-                                    let file_path = PathBuf::from(path);
-                                    if let Some(dir_path) = file_path.parent() {
-                                        if let Some(dir_str) = dir_path.to_str() {
-                                            opener::open(dir_str).unwrap_or_else(|e| {
-                                                eprintln!("Failed to open directory: {}", e);
-                                            });
-                                        }
-                                    }
+                            // Entries sharing a directory open it once.
+                            let dirs: BTreeSet<PathBuf> =
+                                action_targets(&marked, &search.results, &results_state)
+                                    .iter()
+                                    .filter_map(|path| {
+                                        PathBuf::from(path).parent().map(PathBuf::from)
+                                    })
+                                    .collect();
+                            for dir_path in dirs {
+                                if let Some(dir_str) = dir_path.to_str() {
+                                    opener::open(dir_str).unwrap_or_else(|e| {
+                                        eprintln!("Failed to open directory: {}", e);
+                                    });
                                 }
                             }
                         }
@@ -307,7 +600,53 @@ fn run_app<B: Backend>(
     }
 }
 
-fn open_file_with_editor(path: &str, preferred_editor: Option<String>) -> Result<()> {
+fn copy_text(path: &str, target: &CopyTarget) -> String {
+    let path = Path::new(path);
+    let copied = match target {
+        CopyTarget::Absolute => path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+        CopyTarget::Relative => match (path::absolute(path), std::env::current_dir()) {
+            (Ok(path), Ok(current_dir)) => clipboard::relative_path(&path, &current_dir),
+            _ => path.to_path_buf(),
+        },
+        CopyTarget::Basename => path.file_name().map(PathBuf::from).unwrap_or_default(),
+        CopyTarget::Parent => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    copied.to_string_lossy().into_owned()
+}
+
+// Says how old the index is once the configured root that was indexed longest ago is stale.
+fn index_age_notice(conn: &Connection, config: &Config) -> Option<String> {
+    let oldest = db::last_index_runs(conn)
+        .ok()?
+        .into_iter()
+        // Without configured paths the current directory is indexed, so any run counts.
+        .filter(|run| config.include.is_empty() || config.include.contains(&run.root))
+        .map(|run| run.finished_at)
+        .min()?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let age = now - oldest;
+    (age >= STALE_INDEX_AGE).then(|| format!("index is {} old", format_age(age)))
+}
+
+// Marked entries if there are any, otherwise the selected one.
+fn action_targets(
+    marked: &BTreeSet<String>,
+    search_results: &[ScoredEntry],
+    results_state: &ListState,
+) -> Vec<String> {
+    if !marked.is_empty() {
+        return marked.iter().cloned().collect();
+    }
+    results_state
+        .selected()
+        .and_then(|i| search_results.get(i))
+        .map(|result| vec![result.entry.path.clone()])
+        .unwrap_or_default()
+}
+
+pub fn open_file_with_editor(paths: &[String], preferred_editor: Option<String>) -> Result<()> {
     let editors = if let Some(editor) = preferred_editor {
         vec![
             editor,
@@ -320,7 +659,7 @@ fn open_file_with_editor(path: &str, preferred_editor: Option<String>) -> Result
     };
 
     for editor in editors {
-        match Command::new(&editor).arg(path).status() {
+        match Command::new(&editor).args(paths).status() {
             Ok(status) if status.success() => return Ok(()),
             _ => continue,
         }
@@ -329,27 +668,50 @@ fn open_file_with_editor(path: &str, preferred_editor: Option<String>) -> Result
 }
 
 // Helper function to create styled spans for highlighting search terms
-fn create_highlighted_spans(text: &str, term: &str, highlight_color: &Color) -> Vec<Span<'static>> {
+fn create_highlighted_spans(
+    text: &str,
+    words: &[String],
+    regex: Option<&Regex>,
+    mode: MatchMode,
+    highlight_color: &Color,
+) -> Vec<Span<'static>> {
     let mut spans = Vec::new();
 
-    if term.is_empty() {
-        spans.push(Span::raw(text.to_string()));
-        return spans;
-    }
-
-    let words: Vec<&str> = term.split_whitespace().collect();
-    if words.is_empty() {
+    if words.is_empty() && regex.is_none() {
         spans.push(Span::raw(text.to_string()));
         return spans;
     }
 
     let mut matches = Vec::new();
-    let text_lower = text.to_lowercase();
+    let (text_lower, sources) = lowercase_with_sources(text);
+
+    if let Some(regex) = regex {
+        for m in regex.find_iter(text).filter(|m| !m.is_empty()) {
+            matches.push((m.start(), m.end()));
+        }
+    }
 
     for word in words {
-        let word_lower = word.to_lowercase();
-        for (start, _) in text_lower.match_indices(&word_lower) {
-            matches.push((start, start + word.len(), word));
+        match mode {
+            MatchMode::Substring | MatchMode::Regex => {
+                // Wildcards match anything, so only the literal pieces around them are highlighted.
+                for piece in word.split(['*', '?']).filter(|piece| !piece.is_empty()) {
+                    let piece_lower: String = piece.chars().flat_map(char::to_lowercase).collect();
+                    for (start, _) in text_lower.match_indices(&piece_lower) {
+                        let end = start + piece_lower.len() - 1;
+                        matches.push((sources[start].0, sources[end].1));
+                    }
+                }
+            }
+            MatchMode::Fuzzy => {
+                // Highlight exactly the characters the fuzzy matcher picked.
+                if let Some((_, positions)) = rank::fuzzy_match(text, word) {
+                    for start in positions {
+                        let len = text[start..].chars().next().map_or(1, char::len_utf8);
+                        matches.push((start, start + len));
+                    }
+                }
+            }
         }
     }
 
@@ -357,7 +719,7 @@ fn create_highlighted_spans(text: &str, term: &str, highlight_color: &Color) ->
     matches.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
 
     let mut last_end = 0;
-    for (start, end, _matched_word) in matches {
+    for (start, end) in matches {
         // Skip if this match is completely contained within a previous match
         if start >= last_end {
             // Add text before the current match
@@ -381,15 +743,39 @@ fn create_highlighted_spans(text: &str, term: &str, highlight_color: &Color) ->
     spans
 }
 
+// `text` lowercased, with the range in `text` of the character each byte came from. Lowercasing
+// can change a character's length (`İ` takes 2 bytes, its lowercase 3), so offsets into the
+// lowercased text can't be used to slice `text` directly.
+fn lowercase_with_sources(text: &str) -> (String, Vec<(usize, usize)>) {
+    let mut lower = String::with_capacity(text.len());
+    let mut sources = Vec::with_capacity(text.len());
+    for (start, c) in text.char_indices() {
+        let end = start + c.len_utf8();
+        for lower_c in c.to_lowercase() {
+            lower.push(lower_c);
+            sources.extend(std::iter::repeat_n((start, end), lower_c.len_utf8()));
+        }
+    }
+    (lower, sources)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn ui<B: Backend>(
     f: &mut Frame<B>,
     search_input: &str,
     cursor_position: &mut usize,
-    search_results: &[String],
+    search_results: &[ScoredEntry],
     results_state: &mut ListState,
+    results_offset: &mut usize,
     focus: &Focus,
     highlight_color: &Color,
     error_message: &Option<String>,
+    status_message: &Option<String>,
+    index_notice: &Option<String>,
+    search_options: &SearchOptions,
+    searching: bool,
+    preview: &PreviewPane,
+    marked: &BTreeSet<String>,
 ) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
@@ -425,11 +811,78 @@ fn ui<B: Backend>(
         Focus::Results => Style::default().fg(Color::Green),
         _ => Style::default(),
     };
-    let results: Vec<ListItem> = search_results
+    let (match_mode, query_text) = db::split_mode_prefix(search_input, search_options.mode);
+    let (highlight_words, highlight_regex) = if match_mode == MatchMode::Regex {
+        (Vec::new(), db::compile_regex(query_text).ok())
+    } else {
+        let words = query::parse(query_text)
+            .map(|query| query.text_terms())
+            .unwrap_or_default();
+        (words, None)
+    };
+
+    let (results_area, preview_area) = if preview.enabled {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+            .split(chunks[1]);
+        (columns[0], Some(columns[1]))
+    } else {
+        (chunks[1], None)
+    };
+
+    // Only rows on screen are built: doing every result each frame stalls input on large result sets.
+    // `results_offset` keeps the window still until the selection moves past either edge.
+    let list_height = (results_area.height.saturating_sub(2) as usize).max(1);
+    let selected = results_state
+        .selected()
+        .filter(|&i| i < search_results.len());
+    if let Some(selected) = selected {
+        if selected < *results_offset {
+            *results_offset = selected;
+        } else if selected >= *results_offset + list_height {
+            *results_offset = selected + 1 - list_height;
+        }
+    }
+    *results_offset = (*results_offset).min(search_results.len().saturating_sub(list_height));
+    let visible_end = (*results_offset + list_height).min(search_results.len());
+    let mut visible_state = ListState::default();
+    visible_state.select(selected.map(|i| i - *results_offset));
+
+    let results: Vec<ListItem> = search_results[*results_offset..visible_end]
         .iter()
-        .map(|item| {
+        .map(|ScoredEntry { entry: item, .. }| {
+            let mut spans = vec![Span::styled(
+                format!(
+                    "{}{:>7}  {:<16}  ",
+                    if marked.contains(&item.path) {
+                        "*"
+                    } else {
+                        " "
+                    },
+                    if item.kind == Some(EntryKind::Dir) {
+                        "dir".to_string()
+                    } else {
+                        format_size(item.size)
+                    },
+                    format_mtime(item.mtime)
+                ),
+                Style::default().fg(Color::Gray),
+            )];
             // Use the search_input for highlighting, not the whole item
-            let spans = create_highlighted_spans(item, search_input, highlight_color);
+            spans.extend(create_highlighted_spans(
+                &item.path,
+                &highlight_words,
+                highlight_regex.as_ref(),
+                match_mode,
+                highlight_color,
+            ));
+            if let Some(target) = &item.link_target {
+                spans.push(Span::styled(
+                    format!(" -> {}", target),
+                    Style::default().fg(Color::Gray),
+                ));
+            }
             ListItem::new(Text::from(Spans::from(spans)))
         })
         .collect();
@@ -442,7 +895,24 @@ fn ui<B: Backend>(
                 .border_style(results_style),
         )
         .highlight_style(Style::default().bg(*highlight_color));
-    f.render_stateful_widget(results_list, chunks[1], results_state);
+    f.render_stateful_widget(results_list, results_area, &mut visible_state);
+
+    if let Some(area) = preview_area {
+        // Scattered fuzzy matches mean little in file contents, so the words are highlighted as written.
+        let preview_mode = match match_mode {
+            MatchMode::Fuzzy => MatchMode::Substring,
+            mode => mode,
+        };
+        render_preview(
+            f,
+            area,
+            preview.current.as_ref(),
+            &highlight_words,
+            highlight_regex.as_ref(),
+            preview_mode,
+            highlight_color,
+        );
+    }
 
     let mut summary_text = if search_results.is_empty() {
         "0 items".to_string()
@@ -454,10 +924,28 @@ fn ui<B: Backend>(
         )
     };
 
+    if !search_options.include_dirs {
+        summary_text.push_str(" (files only)");
+    }
+    if match_mode == MatchMode::Regex {
+        summary_text.push_str(" (regex)");
+    }
+    if !marked.is_empty() {
+        summary_text.push_str(&format!(" ({} marked)", marked.len()));
+    }
+    if let Some(notice) = index_notice {
+        summary_text.push_str(&format!(" ({})", notice));
+    }
+    if searching {
+        summary_text.push_str(" searching…");
+    }
+
     // Add shortcuts based on focus
     let shortcuts_text = match focus {
-        Focus::Search => " | Esc: Quit",
-        Focus::Results => " | Enter/o: Open | e: Edit | d: Dir | Tab: Search | Esc: Quit",
+        Focus::Search => " | Ctrl-D: Dirs | Ctrl-R: Regex | Ctrl-P: Preview | Esc: Quit",
+        Focus::Results => {
+            " | Enter/o: Open | e: Edit | d: Parent dir | Space/*: Mark | P: Print marked | y/Y/b/p: Copy path/relative/name/parent | Ctrl-D: Dirs | Ctrl-R: Regex | Ctrl-P: Preview | Tab: Search | Esc: Quit"
+        }
     };
     summary_text.push_str(shortcuts_text);
 
@@ -470,5 +958,128 @@ fn ui<B: Backend>(
         // Use err.as_str() to convert String to &str for Paragraph::new
         let error_paragraph = Paragraph::new(err.as_str()).style(error_style);
         f.render_widget(error_paragraph, chunks[3]);
+    } else if let Some(status) = status_message {
+        let status_paragraph =
+            Paragraph::new(status.as_str()).style(Style::default().fg(Color::Green));
+        f.render_widget(status_paragraph, chunks[3]);
+    }
+}
+
+fn render_preview<B: Backend>(
+    f: &mut Frame<B>,
+    area: Rect,
+    preview: Option<&(String, Preview)>,
+    words: &[String],
+    regex: Option<&Regex>,
+    mode: MatchMode,
+    highlight_color: &Color,
+) {
+    let dimmed = Style::default().fg(Color::Gray);
+    let (title, lines) = match preview {
+        None => (String::new(), vec![]),
+        Some((path, preview)) => {
+            let title = PathBuf::from(path)
+                .file_name()
+                .map_or_else(|| path.clone(), |name| name.to_string_lossy().into_owned());
+            let lines = match preview {
+                Preview::Text { lines, truncated } => {
+                    let mut rows: Vec<Spans> = lines
+                        .iter()
+                        .map(|line| {
+                            Spans::from(create_highlighted_spans(
+                                line,
+                                words,
+                                regex,
+                                mode,
+                                highlight_color,
+                            ))
+                        })
+                        .collect();
+                    if *truncated {
+                        rows.push(Spans::from(Span::styled("…", dimmed)));
+                    }
+                    rows
+                }
+                Preview::Directory { entries, total } => {
+                    let mut rows: Vec<Spans> = entries
+                        .iter()
+                        .map(|entry| Spans::from(entry.as_str().to_string()))
+                        .collect();
+                    if *total == 0 {
+                        rows.push(Spans::from(Span::styled("(empty directory)", dimmed)));
+                    } else if *total > entries.len() {
+                        rows.push(Spans::from(Span::styled(
+                            format!("… and {} more", total - entries.len()),
+                            dimmed,
+                        )));
+                    }
+                    rows
+                }
+                Preview::Binary {
+                    size,
+                    kind,
+                    permissions,
+                } => vec![
+                    Spans::from(format!(
+                        "Size:        {} ({} bytes)",
+                        format_size(Some(*size)),
+                        size
+                    )),
+                    Spans::from(format!("Type:        {}", kind)),
+                    Spans::from(format!("Permissions: {}", permissions)),
+                ],
+                Preview::Unavailable(reason) => vec![Spans::from(Span::styled(
+                    format!("Can't preview: {}", reason),
+                    Style::default().fg(Color::Red),
+                ))],
+            };
+            (title, lines)
+        }
+    };
+
+    let paragraph =
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title));
+    f.render_widget(paragraph, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn highlighted(text: &str, word: &str) -> Vec<(String, bool)> {
+        let words = vec![word.to_string()];
+        create_highlighted_spans(text, &words, None, MatchMode::Substring, &Color::Yellow)
+            .into_iter()
+            .map(|span| (span.content.into_owned(), span.style.bg.is_some()))
+            .collect()
+    }
+
+    #[test]
+    fn highlights_after_characters_whose_lowercase_is_longer() {
+        assert_eq!(
+            highlighted("İxé", "é"),
+            [("İx".to_string(), false), ("é".to_string(), true)]
+        );
+        assert_eq!(
+            highlighted("İSTANBUL.txt", "stan"),
+            [
+                ("İ".to_string(), false),
+                ("STAN".to_string(), true),
+                ("BUL.txt".to_string(), false)
+            ]
+        );
+    }
+
+    #[test]
+    fn highlights_whole_characters_when_matching_part_of_a_lowercase_expansion() {
+        // `İ` lowercases to `i` followed by a combining dot.
+        assert_eq!(
+            highlighted("aİb", "i"),
+            [
+                ("a".to_string(), false),
+                ("İ".to_string(), true),
+                ("b".to_string(), false)
+            ]
+        );
     }
 }
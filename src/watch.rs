@@ -0,0 +1,243 @@
+use crate::config::Config;
+use crate::db::{self, InsertOutcome, WalkError};
+use crate::indexing;
+use eyre::Result;
+use glob::Pattern;
+use ignore::gitignore::GitignoreBuilder;
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+
+struct WatchContext<'a> {
+    conn: &'a Connection,
+    roots: Vec<PathBuf>,
+    ignore_patterns: Arc<Vec<Pattern>>,
+    depth: usize,
+    respect_ignore_files: bool,
+    verbose: bool,
+}
+
+pub fn watch_files(
+    conn: &Connection,
+    config: &Config,
+    paths_to_watch: &[String],
+    verbose: bool,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx)?;
+
+    let mut roots = Vec::new();
+    for path in paths_to_watch {
+        let root = PathBuf::from(path);
+        if !root.is_dir() {
+            println!("Skipping missing path: {}", path);
+            continue;
+        }
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+        roots.push(root);
+    }
+
+    // The watcher is started before the initial sync so that changes made while it runs aren't lost.
+    for root in &roots {
+        if let Some(root_str) = root.to_str() {
+            println!("Indexing path: {}", root_str);
+            indexing::index_files(conn, config, root_str, verbose)?;
+        }
+    }
+
+    let context = WatchContext {
+        conn,
+        roots,
+        ignore_patterns: Arc::new(indexing::compile_ignore_patterns(config)?),
+        depth: config.depth,
+        respect_ignore_files: config.respect_ignore_files,
+        verbose,
+    };
+
+    println!(
+        "Watching {} paths for changes. Press Ctrl-C to stop.",
+        context.roots.len()
+    );
+    for res in rx {
+        match res {
+            // Updates wait for other writers, such as an index run, to commit. One that still fails
+            // only loses that event; the next full index run catches up on it.
+            Ok(event) => {
+                let paths = event.paths.clone();
+                if let Err(e) = context.apply_event(event) {
+                    eprintln!("Failed to update the index for {:?}: {:?}", paths, e);
+                }
+            }
+            Err(e) => eprintln!("Watch error: {:?}", e),
+        }
+    }
+
+    Ok(())
+}
+
+impl WatchContext<'_> {
+    fn apply_event(&self, event: Event) -> Result<()> {
+        match event.kind {
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                for path in &event.paths {
+                    self.add_path(path)?;
+                }
+            }
+            EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Metadata(_)) => {
+                // Refresh size and mtime; directory contents are covered by their own events.
+                for path in event.paths.iter().filter(|p| p.is_file()) {
+                    self.add_path(path)?;
+                }
+            }
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                for path in &event.paths {
+                    self.remove_path(path)?;
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let [from, to] = event.paths.as_slice() {
+                    self.remove_path(from)?;
+                    self.add_path(to)?;
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                // The backend couldn't tell which side of the rename this is, so check the filesystem.
+                for path in &event.paths {
+                    // `exists` follows symlinks, which would take a broken link for a deleted one.
+                    if path.symlink_metadata().is_ok() {
+                        self.add_path(path)?;
+                    } else {
+                        self.remove_path(path)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn add_path(&self, path: &Path) -> Result<()> {
+        let Some(root) = self.root_for(path) else {
+            return Ok(());
+        };
+        let Some(remaining_depth) = self.remaining_depth(root, path) else {
+            return Ok(());
+        };
+        if self.is_ignored(root, path) {
+            if self.verbose {
+                println!("Skipping ignored path: {:?}", path);
+            }
+            return Ok(());
+        }
+
+        // A directory that was created or moved in brings its whole subtree with it.
+        let ignore_patterns = Arc::clone(&self.ignore_patterns);
+        let filter_root = root.clone();
+        let tx = self.conn.unchecked_transaction()?;
+        for entry in indexing::walk_builder(path, self.respect_ignore_files)
+            .max_depth(Some(remaining_depth))
+            .filter_entry(move |entry| {
+                !indexing::is_ignored(&ignore_patterns, &filter_root, entry.path())
+            })
+            .build()
+        {
+            // There's no index run to record these with, so they're reported as they happen;
+            // the next `quickfind index` records them for `stats`.
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    report_walk_error(&indexing::walk_error(&err));
+                    continue;
+                }
+            };
+            let entry_path = entry.path();
+            if entry_path.is_file() || entry_path.is_dir() || entry.path_is_symlink() {
+                match indexing::read_entry(entry_path) {
+                    Some(file_entry) => {
+                        let outcome = db::insert_file(&tx, &file_entry)?;
+                        if matches!(outcome, InsertOutcome::Added) && self.verbose {
+                            println!("Added: {}", file_entry.path);
+                        }
+                    }
+                    None => report_walk_error(&indexing::non_utf8_error(entry_path)),
+                }
+            }
+        }
+        db::flush_path_index(&tx)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn remove_path(&self, path: &Path) -> Result<()> {
+        if self.root_for(path).is_none() {
+            return Ok(());
+        }
+        if let Some(path_str) = path.to_str() {
+            let removed = db::delete_tree(self.conn, path_str)?;
+            if removed > 0 && self.verbose {
+                println!("Removed: {} ({} entries)", path_str, removed);
+            }
+        }
+        Ok(())
+    }
+
+    // Picks the most specific include root when roots are nested.
+    fn root_for(&self, path: &Path) -> Option<&PathBuf> {
+        self.roots
+            .iter()
+            .filter(|root| path.starts_with(root))
+            .max_by_key(|root| root.components().count())
+    }
+
+    // Mirrors `max_depth` in `indexing::index_files`: the root itself is depth 0.
+    fn remaining_depth(&self, root: &Path, path: &Path) -> Option<usize> {
+        let depth = path.strip_prefix(root).ok()?.components().count();
+        self.depth.checked_sub(depth)
+    }
+
+    // The walk prunes at the first ignored directory, so every ancestor up to the root is checked too.
+    fn is_ignored(&self, root: &Path, path: &Path) -> bool {
+        let ignored_by_config = path
+            .ancestors()
+            .take_while(|ancestor| *ancestor != root)
+            .any(|ancestor| indexing::is_ignored(&self.ignore_patterns, root, ancestor));
+        ignored_by_config || (self.respect_ignore_files && ignored_by_ignore_files(path))
+    }
+}
+
+fn report_walk_error(error: &WalkError) {
+    eprintln!(
+        "Could not index {}: {} ({})",
+        error.path, error.kind, error.message
+    );
+}
+
+// The walker only applies ignore files to what's beneath its starting point, so the path an event
+// names is checked by hand. The closest directory with a matching rule wins, as it does in the walk.
+fn ignored_by_ignore_files(path: &Path) -> bool {
+    let is_dir = path.is_dir();
+    for dir in path.ancestors().skip(1) {
+        let mut builder = GitignoreBuilder::new(dir);
+        // Later files take precedence within a directory, matching the walker's order.
+        for name in [".gitignore", ".ignore", indexing::IGNORE_FILENAME] {
+            let ignore_file = dir.join(name);
+            if ignore_file.is_file() {
+                builder.add(ignore_file);
+            }
+        }
+        let Ok(matcher) = builder.build() else {
+            continue;
+        };
+
+        let matched = matcher.matched_path_or_any_parents(path, is_dir);
+        if matched.is_ignore() {
+            return true;
+        }
+        if matched.is_whitelist() {
+            return false;
+        }
+    }
+    false
+}